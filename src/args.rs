@@ -0,0 +1,258 @@
+use std::path::{Path, PathBuf};
+
+use crate::format::Encoding;
+use crate::syntax;
+
+// command line options
+
+pub const USAGE: &str = "usage: jedit [options] [+line] [file[:line[:col]] | -]...
+
+  -                    read a buffer from stdin, written to stdout on quit
+  +line                put the cursor on line in the next file
+  --readonly           open files read-only
+  --syntax <name>      highlight files as name
+  --config <path>      read settings from path instead of config.toml
+                       and keys from keymap.toml next to it
+  --theme <name>       use the theme name
+  --encoding <name>    read files as utf-8, utf-16le, utf-16be or latin-1
+  --tty                draw in the terminal instead of a window
+  --version            print the version
+  --help               print this";
+
+// a file to open and where to put the cursor
+// lines and columns are 1-based
+pub struct File {
+    // "-" for stdin
+    pub path: String,
+    pub line: Option<usize>,
+    pub col: Option<usize>
+}
+
+pub struct Args {
+    pub files: Vec<File>,
+    pub readonly: bool,
+    pub syntax: Option<String>,
+    pub config: Option<PathBuf>,
+    pub theme: Option<String>,
+    pub encoding: Option<Encoding>,
+    // the terminal instead of a window
+    pub tty: bool,
+    pub version: bool,
+    pub help: bool
+}
+
+fn number(string: &str) -> Option<usize> {
+    match string.parse::<usize>() {
+        Ok(n) if n > 0 => Some(n),
+        _ => None
+    }
+}
+
+// file:line:col or file:line for paths that
+// don't exist with the suffix
+fn split_position(arg: &str) -> File {
+    let whole = File {
+        path: String::from(arg),
+        line: None,
+        col: None
+    };
+    if Path::new(arg).exists() {
+        return whole;
+    }
+
+    let parts: Vec<&str> = arg.rsplitn(3, ':').collect();
+    match parts.as_slice() {
+        [col, line, path] if !path.is_empty() => match (number(line), number(col)) {
+            (Some(line), Some(col)) => File {
+                path: String::from(*path),
+                line: Some(line),
+                col: Some(col)
+            },
+            // a colon in the name
+            (None, Some(line)) => File {
+                path: format!("{}:{}", path, parts[1]),
+                line: Some(line),
+                col: None
+            },
+            _ => whole
+        },
+        [line, path] if !path.is_empty() => match number(line) {
+            Some(line) => File {
+                path: String::from(*path),
+                line: Some(line),
+                col: None
+            },
+            None => whole
+        },
+        _ => whole
+    }
+}
+
+impl Args {
+    // args without the program name
+    pub fn parse<I: Iterator<Item = String>>(mut args: I) -> Result<Args, String> {
+        let mut parsed = Args {
+            files: Vec::new(),
+            readonly: false,
+            syntax: None,
+            config: None,
+            theme: None,
+            encoding: None,
+            tty: false,
+            version: false,
+            help: false
+        };
+        // from +line, for the next file
+        let mut line_maybe = None;
+        let mut options = true;
+
+        while let Some(arg) = args.next() {
+            if options && arg.starts_with("--") && arg.len() > 2 {
+                // --name value or --name=value
+                let (name, inline) = match arg.find('=') {
+                    Some(i) => (&arg[..i], Some(String::from(&arg[i + 1..]))),
+                    None => (arg.as_str(), None)
+                };
+                let value = |args: &mut I| match inline.clone().or_else(|| args.next()) {
+                    Some(value) => Ok(value),
+                    None => Err(format!("{} needs a value", name))
+                };
+
+                match name {
+                    "--readonly" => parsed.readonly = true,
+                    "--syntax" => {
+                        let name = value(&mut args)?;
+                        if syntax::by_name(name.as_str()).is_none() {
+                            return Err(format!("unknown syntax {}", name));
+                        }
+                        parsed.syntax = Some(name);
+                    },
+                    "--config" => parsed.config = Some(PathBuf::from(value(&mut args)?)),
+                    "--theme" => parsed.theme = Some(value(&mut args)?),
+                    "--encoding" => {
+                        let name = value(&mut args)?;
+                        parsed.encoding = Some(Encoding::by_name(name.as_str()).ok_or_else(|| format!("unknown encoding {}", name))?);
+                    },
+                    "--tty" => parsed.tty = true,
+                    "--version" => parsed.version = true,
+                    "--help" => parsed.help = true,
+                    _ => return Err(format!("unknown option {}", name))
+                }
+                if inline.is_some() && ["--readonly", "--tty", "--version", "--help"].contains(&name) {
+                    return Err(format!("{} takes no value", name));
+                }
+            } else if options && arg == "--" {
+                options = false;
+            } else if options && arg.starts_with('-') && arg != "-" {
+                return Err(format!("unknown option {}", arg));
+            } else if options && arg.starts_with('+') {
+                line_maybe = Some(number(&arg[1..]).ok_or_else(|| format!("not a line number: {}", arg))?);
+            } else {
+                let mut file = if options && arg != "-" {
+                    split_position(arg.as_str())
+                } else {
+                    File {
+                        path: arg,
+                        line: None,
+                        col: None
+                    }
+                };
+                if let Some(line) = line_maybe.take() {
+                    file.line = Some(line);
+                    file.col = None;
+                }
+                parsed.files.push(file);
+            }
+        }
+
+        if line_maybe.is_some() {
+            return Err(String::from("+line needs a file after it"));
+        }
+
+        Ok(parsed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Args, String> {
+        Args::parse(args.iter().map(|x| String::from(*x)))
+    }
+
+    // path, line and col of each file
+    fn files(args: &[&str]) -> Vec<(String, Option<usize>, Option<usize>)> {
+        parse(args).unwrap().files.into_iter().map(|x| (x.path, x.line, x.col)).collect()
+    }
+
+    fn file(path: &str, line: Option<usize>, col: Option<usize>) -> (String, Option<usize>, Option<usize>) {
+        (String::from(path), line, col)
+    }
+
+    #[test]
+    fn plus_line() {
+        assert_eq!(files(&["+12", "no-such-a", "no-such-b"]), vec![
+            file("no-such-a", Some(12), None), file("no-such-b", None, None)
+        ]);
+        // +line wins over a position in the name
+        assert_eq!(files(&["+3", "no-such:7:2"]), vec![file("no-such", Some(3), None)]);
+        assert!(parse(&["+x", "a"]).is_err());
+        assert_eq!(parse(&["a", "+4"]).err().unwrap(), "+line needs a file after it");
+    }
+
+    #[test]
+    fn positions() {
+        assert_eq!(files(&["no-such.rs:10"]), vec![file("no-such.rs", Some(10), None)]);
+        assert_eq!(files(&["no-such.rs:10:5"]), vec![file("no-such.rs", Some(10), Some(5))]);
+        assert_eq!(files(&["no-such.rs:x"]), vec![file("no-such.rs:x", None, None)]);
+        assert_eq!(files(&[":10"]), vec![file(":10", None, None)]);
+    }
+
+    #[test]
+    fn colons_in_names() {
+        // the last number is the line when the middle isn't one
+        assert_eq!(files(&["a:b:3"]), vec![file("a:b", Some(3), None)]);
+        assert_eq!(files(&["a:b:c"]), vec![file("a:b:c", None, None)]);
+    }
+
+    #[test]
+    fn zero_is_not_a_position() {
+        assert_eq!(files(&["no-such.rs:0"]), vec![file("no-such.rs:0", None, None)]);
+        assert_eq!(files(&["no-such.rs:1:0"]), vec![file("no-such.rs:1:0", None, None)]);
+        assert_eq!(parse(&["+0", "a"]).err().unwrap(), "not a line number: +0");
+    }
+
+    #[test]
+    fn existing_files_keep_their_name() {
+        // the crate manifest is there when tests run
+        let path = concat!(env!("CARGO_MANIFEST_DIR"), "/Cargo.toml");
+        assert_eq!(files(&[path]), vec![file(path, None, None)]);
+    }
+
+    #[test]
+    fn stdin_and_double_dash() {
+        assert_eq!(files(&["-"]), vec![file("-", None, None)]);
+        assert_eq!(files(&["--", "--tty", "-x:3"]), vec![file("--tty", None, None), file("-x:3", None, None)]);
+    }
+
+    #[test]
+    fn options() {
+        let args = parse(&["--readonly", "--tty", "--syntax", "rust", "--theme=light", "--encoding", "latin-1"]).unwrap();
+        assert!(args.readonly && args.tty);
+        assert_eq!(args.syntax.as_deref(), Some("rust"));
+        assert_eq!(args.theme.as_deref(), Some("light"));
+        assert_eq!(args.encoding, Some(Encoding::Latin1));
+    }
+
+    #[test]
+    fn option_errors() {
+        assert_eq!(parse(&["--theme"]).err().unwrap(), "--theme needs a value");
+        assert_eq!(parse(&["--config"]).err().unwrap(), "--config needs a value");
+        assert_eq!(parse(&["--syntax", "cobol"]).err().unwrap(), "unknown syntax cobol");
+        assert_eq!(parse(&["--encoding", "ebcdic"]).err().unwrap(), "unknown encoding ebcdic");
+        assert_eq!(parse(&["--tty=1"]).err().unwrap(), "--tty takes no value");
+        assert_eq!(parse(&["--nope"]).err().unwrap(), "unknown option --nope");
+        assert_eq!(parse(&["-x"]).err().unwrap(), "unknown option -x");
+    }
+}
@@ -1,9 +1,20 @@
+use std::cell::Cell;
+use std::cmp;
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
 use std::slice::Iter;
 use std::mem;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
 
 use jvec::JVec;
 
+use crate::format::Format;
+use crate::large::LargeFile;
 use crate::syntax::Syntax;
+use crate::TAB_SIZE;
 
 #[derive(Clone, Debug)]
 pub struct Char {
@@ -17,7 +28,7 @@ impl Char {
     // for debugging
     pub fn new(chr: char) -> Char {
         Char {
-            chr: chr,
+            chr,
             bg: 0,
             fg: 1,
             font: 0
@@ -25,32 +36,481 @@ impl Char {
     }
 }
 
+// chars of a file line like a Buffer stores them
+// leading tabs are tab_width chars wide
+pub fn stored_chars(line: &str, tab_width: usize) -> Vec<char> {
+    let tabs = line.chars().take_while(|x| *x == '\t').count();
+
+    let mut chars = vec!['\t'; tabs * tab_width];
+    chars.extend(line.chars().skip(tabs));
+    chars
+}
+
+// a change to the stored chars
+// undo makes the opposite one
+#[derive(Clone, Debug, PartialEq)]
+pub enum Edit {
+    Insert(usize, usize, char),
+    Remove(usize, usize, char),
+    // the chars of line y from x on move to a new line
+    Split(usize, usize),
+    // line y + 1 goes onto the end of line y which was x long
+    Join(usize, usize),
+    InsertLine(usize, Vec<char>),
+    RemoveLine(usize, Vec<char>),
+    // the text before and after set_text
+    Replace(String, String)
+}
+
+impl Edit {
+    pub fn opposite(&self) -> Edit {
+        match self {
+            Edit::Insert(x, y, chr) => Edit::Remove(*x, *y, *chr),
+            Edit::Remove(x, y, chr) => Edit::Insert(*x, *y, *chr),
+            Edit::Split(x, y) => Edit::Join(*x, *y),
+            Edit::Join(x, y) => Edit::Split(*x, *y),
+            Edit::InsertLine(y, chars) => Edit::RemoveLine(*y, chars.clone()),
+            Edit::RemoveLine(y, chars) => Edit::InsertLine(*y, chars.clone()),
+            Edit::Replace(before, after) => Edit::Replace(after.clone(), before.clone())
+        }
+    }
+
+    // about how many bytes the edit keeps
+    pub fn size(&self) -> usize {
+        match self {
+            Edit::InsertLine(_, chars) | Edit::RemoveLine(_, chars) => chars.len(),
+            Edit::Replace(before, after) => before.len() + after.len(),
+            _ => 1
+        }
+    }
+}
+
 // buffer will handle
 // syntax update callbacks
 // later
 pub struct Buffer {
     buffer: JVec<JVec<Char>>,
-    syntax: Option<Box<Syntax>>
+    syntax: Option<Box<dyn Syntax>>,
+
+    // None for scratch buffers
+    path: Option<PathBuf>,
+    // read from stdin and written to stdout on quit
+    pipe: bool,
+    // edits are no-ops that set refused
+    // so the editor can say why
+    readonly: bool,
+    refused: bool,
+    // edited since the last save
+    // edits can lead back to the saved text
+    edited: bool,
+    // hash of the text when it was last saved or loaded
+    saved: u64,
+    // counts changes to the text
+    version: u64,
+    // version and hash of the text last hashed
+    // so modified doesn't read every line each redraw
+    hashed: Cell<Option<(u64, u64)>>,
+    // changes since take_edits for undo
+    // set_text logs one Replace instead of every char
+    edits: Vec<Edit>,
+    logging: bool,
+    // modification time and text of the file
+    // when it was last read or written
+    disk_time: Option<SystemTime>,
+    disk_text: String,
+    // line endings and such of the file
+    format: Format,
+
+    // chars stored for each leading tab
+    tab_width: usize,
+    // tab inserts spaces
+    expand_tabs: bool,
+
+    // Some in large file mode where buffer only
+    // holds the lines from window_start on
+    large: Option<LargeFile>,
+    window_start: usize,
+    // for lines outside the window
+    no_line: Option<JVec<Char>>
 }
 
 impl Buffer {
-    pub fn new(syntax: Option<Box<Syntax>>) -> Buffer {
+    pub fn new(syntax: Option<Box<dyn Syntax>>) -> Buffer {
         Buffer {
             buffer: JVec::new(),
-            syntax: syntax
+            syntax,
+
+            path: None,
+            pipe: false,
+            readonly: false,
+            refused: false,
+            edited: false,
+            saved: 0,
+            version: 0,
+            hashed: Cell::new(None),
+            edits: Vec::new(),
+            logging: true,
+            disk_time: None,
+            disk_text: String::new(),
+            format: Format::new(),
+
+            tab_width: TAB_SIZE,
+            expand_tabs: false,
+
+            large: None,
+            window_start: 0,
+            no_line: None
         }
     }
 
-    pub fn line(&self, y: usize) -> &Option<JVec<Char>> {
-        &self.buffer[y]
+    pub fn tab_width(&self) -> usize {
+        self.tab_width
+    }
+
+    pub fn expand_tabs(&self) -> bool {
+        self.expand_tabs
+    }
+
+    pub fn set_tabs(&mut self, tab_width: usize, expand_tabs: bool) {
+        self.tab_width = tab_width;
+        self.expand_tabs = expand_tabs;
+    }
+
+    pub fn path(&self) -> Option<&Path> {
+        self.path.as_deref()
+    }
+
+    pub fn set_path(&mut self, path: Option<PathBuf>) {
+        self.path = path;
+    }
+
+    pub fn is_large(&self) -> bool {
+        self.large.is_some()
+    }
+
+    pub fn set_large(&mut self, large: LargeFile) {
+        self.large = Some(large);
+        self.buffer = JVec::new();
+        self.window_start = 0;
+    }
+
+    // false while lines of a large file are still being found
+    pub fn is_indexed(&self) -> bool {
+        self.large.as_ref().is_none_or(|x| x.is_indexed())
+    }
+
+    // finds more lines of a large file
+    // false when there is nothing to do
+    pub fn index_more(&mut self) -> bool {
+        self.large.as_mut().is_some_and(|x| x.index_more())
+    }
+
+    pub fn index_all(&mut self) {
+        if let Some(large) = self.large.as_mut() {
+            large.index_all();
+        }
+    }
+
+    // decodes the lines of a large file around y
+    // rows before it and twice as many after
+    pub fn load_around(&mut self, y: usize, rows: usize) {
+        let large = match self.large.as_mut() {
+            Some(large) => large,
+            None => return
+        };
+        large.index_to(y + 2 * rows);
+        let start = y.saturating_sub(rows);
+        let end = cmp::min(y + 2 * rows, large.lines());
+        if start >= self.window_start && end <= self.window_start + self.buffer.len() {
+            return;
+        }
+
+        let mut window = JVec::new();
+        for line_y in start..end {
+            let mut line = JVec::new();
+            for chr in large.chars(line_y, self.tab_width).unwrap_or_default() {
+                line.push(Some(Char::new(chr)));
+            }
+            window.push(Some(line));
+        }
+        self.buffer = window;
+        self.window_start = start;
+    }
+
+    pub fn is_pipe(&self) -> bool {
+        self.pipe
+    }
+
+    pub fn set_pipe(&mut self, pipe: bool) {
+        self.pipe = pipe;
+    }
+
+    pub fn is_readonly(&self) -> bool {
+        self.readonly
+    }
+
+    pub fn set_readonly(&mut self, readonly: bool) {
+        self.readonly = readonly;
+    }
+
+    // false for read-only buffers
+    // which then count as refused
+    pub fn check_edit(&mut self) -> bool {
+        if self.readonly {
+            self.refused = true;
+        }
+
+        !self.readonly
+    }
+
+    // whether an edit was refused since the last call
+    pub fn take_refused(&mut self) -> bool {
+        mem::replace(&mut self.refused, false)
+    }
+
+    // for buffer lists
+    pub fn name(&self) -> String {
+        match self.path.as_ref() {
+            Some(path) => path.display().to_string(),
+            None if self.pipe => String::from("[stdin]"),
+            None => String::from("[scratch]")
+        }
+    }
+
+    pub fn disk_time(&self) -> Option<SystemTime> {
+        self.disk_time
+    }
+
+    pub fn disk_text(&self) -> &str {
+        self.disk_text.as_str()
+    }
+
+    pub fn set_disk(&mut self, time: Option<SystemTime>, text: String) {
+        self.disk_time = time;
+        self.disk_text = text;
+    }
+
+    pub fn format(&self) -> Format {
+        self.format
+    }
+
+    // changing the format is an edit
+    pub fn set_format(&mut self, format: Format) {
+        self.format = format;
+        self.edited = true;
+        self.version += 1;
+    }
+
+    pub fn syntax_name(&self) -> &str {
+        match self.syntax.as_ref() {
+            Some(syntax) => syntax.name(),
+            None => "plain"
+        }
+    }
+
+    fn hash(&self) -> u64 {
+        if let Some((version, hash)) = self.hashed.get() {
+            if version == self.version {
+                return hash;
+            }
+        }
+
+        let mut hasher = DefaultHasher::new();
+        for line in self.iter() {
+            if let Some(line) = line {
+                for chr in line.iter().flatten() {
+                    chr.chr.hash(&mut hasher);
+                }
+            }
+            '\n'.hash(&mut hasher);
+        }
+        self.format.hash(&mut hasher);
+
+        let hash = hasher.finish();
+        self.hashed.set(Some((self.version, hash)));
+        hash
+    }
+
+    // edits since the last call
+    pub fn take_edits(&mut self) -> Vec<Edit> {
+        mem::take(&mut self.edits)
+    }
+
+    fn log(&mut self, edit: Edit) {
+        if self.logging {
+            self.edits.push(edit);
+        }
+    }
+
+    // makes the edit again
+    pub fn apply(&mut self, edit: &Edit) {
+        match edit {
+            Edit::Insert(x, y, chr) => self.insert(*x, *y, Char::new(*chr)),
+            Edit::Remove(x, y, _) => {
+                self.remove(*x, *y);
+            },
+            Edit::Split(x, y) => self.split_line(*x, *y),
+            Edit::Join(_, y) => self.join_line(*y),
+            Edit::InsertLine(y, chars) => {
+                self.insert_line(*y, JVec::new());
+                for (x, chr) in chars.iter().enumerate() {
+                    self.insert(x, *y, Char::new(*chr));
+                }
+            },
+            Edit::RemoveLine(y, _) => {
+                self.remove_line(*y);
+            },
+            Edit::Replace(_, after) => self.set_text(after.as_str())
+        }
+    }
+
+    // differs from the saved text
+    pub fn modified(&self) -> bool {
+        self.edited && self.hash() != self.saved
     }
 
-    pub fn line_mut(&mut self, y: usize) -> &mut Option<JVec<Char>> {
-        &mut self.buffer[y]
+    // the current text counts as saved
+    pub fn mark_saved(&mut self) {
+        self.edited = false;
+        self.saved = self.hash();
+    }
+
+    // file contents with indentation
+    // turned back into tabs
+    pub fn text(&self) -> String {
+        let mut string = String::new();
+        for (y, line) in self.iter().enumerate() {
+            if y != 0 {
+                string.push('\n');
+            }
+
+            if let Some(line) = line {
+                let chars: Vec<char> = line.iter().flatten().map(|x| x.chr).collect();
+                // tabs after the indentation are single chars
+                let tabs = chars.iter().take_while(|x| **x == '\t').count();
+                string.extend(vec!['\t'; tabs / self.tab_width + tabs % self.tab_width]);
+                string.extend(&chars[tabs..]);
+            }
+        }
+
+        string
+    }
+
+    // replaces every line with text read from a file
+    // without the auto indent of typing
+    pub fn set_text(&mut self, text: &str) {
+        if !self.check_edit() {
+            return;
+        }
+        let before = if self.logging && self.large.is_none() {
+            self.text()
+        } else {
+            String::new()
+        };
+
+        let logging = mem::replace(&mut self.logging, false);
+        self.clear();
+        for (y, line) in text.split('\n').enumerate() {
+            if text.is_empty() {
+                break;
+            }
+            if y != 0 {
+                self.insert_line(y, JVec::new());
+            }
+            for (x, chr) in stored_chars(line, self.tab_width).into_iter().enumerate() {
+                self.insert(x, y, Char::new(chr));
+            }
+        }
+        self.logging = logging;
+        self.log(Edit::Replace(before, String::from(text)));
+    }
+
+    // first match at or after (x, y)
+    // wraps around the end of the buffer
+    pub fn find(&mut self, pattern: &str, x: usize, y: usize) -> Option<(usize, usize)> {
+        let tab_width = self.tab_width;
+        if let Some(large) = self.large.as_mut() {
+            return large.find(pattern, x, y, tab_width);
+        }
+
+        let pattern: Vec<char> = pattern.chars().collect();
+        let height = self.height();
+        if pattern.is_empty() || height == 0 {
+            return None;
+        }
+
+        for i in 0..=height {
+            let line_y = (y + i) % height;
+            let line: Vec<char> = match self.line(line_y) {
+                Some(line) => line.iter().flatten().map(|x| x.chr).collect(),
+                None => continue
+            };
+            if line.len() < pattern.len() {
+                continue;
+            }
+
+            let start = if i == 0 {
+                x
+            } else {
+                0
+            };
+            for line_x in start..=(line.len() - pattern.len()) {
+                // back on the first line after wrapping
+                if i == height && line_x >= x {
+                    break;
+                }
+                if line[line_x..(line_x + pattern.len())] == pattern[..] {
+                    return Some((line_x, line_y));
+                }
+            }
+        }
+
+        None
+    }
+
+    pub fn save(&mut self) -> io::Result<()> {
+        if self.readonly {
+            return Err(io::Error::new(io::ErrorKind::PermissionDenied, "read-only"));
+        }
+        let path = match self.path.as_ref() {
+            Some(path) => path,
+            None => return Err(io::Error::new(io::ErrorKind::NotFound, "no file name"))
+        };
+        let text = self.text();
+        fs::write(path, self.format.encode(text.as_str())?)?;
+        self.disk_time = fs::metadata(path).and_then(|x| x.modified()).ok();
+        self.disk_text = text;
+        // every line ends with eol now
+        self.format.mixed = false;
+        self.mark_saved();
+
+        Ok(())
+    }
+
+    // removes every line
+    fn clear(&mut self) {
+        if !self.check_edit() {
+            return;
+        }
+        self.buffer = JVec::new();
+        self.edited = true;
+        self.version += 1;
+    }
+
+    // lines of large files outside the window are None
+    pub fn line(&self, y: usize) -> &Option<JVec<Char>> {
+        if self.large.is_none() {
+            return &self.buffer[y];
+        }
+
+        match y.checked_sub(self.window_start) {
+            Some(i) if i < self.buffer.len() => &self.buffer[i],
+            _ => &self.no_line
+        }
     }
 
     pub fn get(&self, x: usize, y: usize) -> Option<&Char> {
-        self.buffer[y].as_ref()?[x].as_ref()
+        self.line(y).as_ref()?[x].as_ref()
     }
 
     pub fn get_mut(&mut self, x: usize, y: usize) -> Option<&mut Char> {
@@ -58,6 +518,9 @@ impl Buffer {
     }
 
     pub fn insert(&mut self, x: usize, y: usize, chr: Char) {
+        if !self.check_edit() {
+            return;
+        }
         let line_maybe = &mut self.buffer[y];
         if line_maybe.is_none() {
             *line_maybe = Some(JVec::new());
@@ -65,12 +528,13 @@ impl Buffer {
         let line = line_maybe.as_mut().unwrap();
         let chr_chr = chr.chr;
         line.insert(x, chr);
+        self.edited = true;
+        self.version += 1;
+        self.log(Edit::Insert(x, y, chr_chr));
 
         // todo rewrite buffer such that there needs to be no
         // self.function(self)
-        let syntax_maybe = mem::replace(&mut self.syntax, None);
-        if syntax_maybe.is_some() {
-            let mut syntax = syntax_maybe.unwrap();
+        if let Some(mut syntax) = self.syntax.take() {
             syntax.insert(x, y, chr_chr, self);
             self.syntax = Some(syntax);
         }
@@ -78,6 +542,9 @@ impl Buffer {
 
     // returns char
     pub fn remove(&mut self, x: usize, y: usize) -> Option<Char> {
+        if !self.check_edit() {
+            return None;
+        }
         let line_maybe = &mut self.buffer[y];
         if line_maybe.is_none() {
             return None;
@@ -85,15 +552,15 @@ impl Buffer {
         let line = line_maybe.as_mut().unwrap();
         let chr_maybe = line.remove(x);
 
-        if chr_maybe.is_some() {
-            let chr = chr_maybe.as_ref().unwrap();
-            
+        if let Some(chr) = chr_maybe.as_ref() {
+            self.edited = true;
+            self.version += 1;
+            self.log(Edit::Remove(x, y, chr.chr));
+
             // todo rewrite buffer such that there needs to be no
             // self.function(self)
-            let syntax_maybe = mem::replace(&mut self.syntax, None);
-            if syntax_maybe.is_some() {
-                let mut syntax = syntax_maybe.unwrap();
-                syntax.remove(x, y, &chr, self);
+            if let Some(mut syntax) = self.syntax.take() {
+                syntax.remove(x, y, chr, self);
                 self.syntax = Some(syntax);
             }
         }
@@ -102,23 +569,195 @@ impl Buffer {
     }
 
     pub fn insert_line(&mut self, y: usize, line: JVec<Char>) {
+        if !self.check_edit() {
+            return;
+        }
+        self.edited = true;
+        self.version += 1;
+        self.log(Edit::InsertLine(y, line.iter().flatten().map(|x| x.chr).collect()));
         self.buffer.insert(y, line);
     }
 
     pub fn remove_line(&mut self, y: usize) -> Option<JVec<Char>> {
-        self.buffer.remove(y)
+        if !self.check_edit() {
+            return None;
+        }
+        self.edited = true;
+        self.version += 1;
+        if y >= self.buffer.len() {
+            return None;
+        }
+        let line_maybe = self.buffer.remove(y);
+        let chars = match line_maybe.as_ref() {
+            Some(line) => line.iter().flatten().map(|x| x.chr).collect(),
+            None => Vec::new()
+        };
+        self.log(Edit::RemoveLine(y, chars));
+        line_maybe
+    }
+
+    // moves the chars from x on to a new line after y
+    pub fn split_line(&mut self, x: usize, y: usize) {
+        if !self.check_edit() {
+            return;
+        }
+        let mut tail = JVec::new();
+        if let Some(line) = self.buffer[y].as_mut() {
+            while line.len() > x {
+                tail.push(line.remove(x));
+            }
+        }
+        self.buffer.insert(y + 1, tail);
+        self.edited = true;
+        self.version += 1;
+        self.log(Edit::Split(x, y));
+    }
+
+    // puts line y + 1 on the end of line y
+    pub fn join_line(&mut self, y: usize) {
+        if !self.check_edit() || y + 1 >= self.buffer.len() {
+            return;
+        }
+        let x = self.line_len(y);
+        let tail_maybe = self.buffer.remove(y + 1);
+        let line_maybe = &mut self.buffer[y];
+        if line_maybe.is_none() {
+            *line_maybe = Some(JVec::new());
+        }
+        let line = line_maybe.as_mut().unwrap();
+        if let Some(tail) = tail_maybe {
+            for chr_maybe in tail {
+                line.push(chr_maybe);
+            }
+        }
+        self.edited = true;
+        self.version += 1;
+        self.log(Edit::Join(x, y));
     }
 
     pub fn height(&self) -> usize {
-        self.buffer.len()
+        match self.large.as_ref() {
+            Some(large) => large.lines(),
+            None => self.buffer.len()
+        }
+    }
+
+    // 0 for empty and missing lines
+    pub fn line_len(&self, y: usize) -> usize {
+        self.line(y).as_ref().map_or(0, |x| x.len())
     }
 
-    // can cause a panic
-    pub fn width(&self, y: usize) -> usize {
-        self.buffer[y].as_ref().unwrap().len()
+    // like line_len but lines past the end are errors
+    pub fn width(&self, y: usize) -> Result<usize, String> {
+        if y >= self.height() {
+            return Err(format!("no line {}", y + 1));
+        }
+
+        Ok(self.line_len(y))
     }
 
-    pub fn iter(&self) -> Iter<Option<JVec<Char>>> {
+    // only the window of large files
+    pub fn iter(&self) -> Iter<'_, Option<JVec<Char>>> {
         self.buffer.iter()
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::syntax;
+
+    // bytes as they come back out of a buffer
+    fn round_trip(bytes: &[u8], syntax: Option<Box<dyn Syntax>>) -> Vec<u8> {
+        let (text, format) = Format::decode(bytes, None).unwrap();
+        let mut buffer = Buffer::new(syntax);
+        buffer.set_text(text.as_str());
+        assert_eq!(buffer.text(), text);
+        format.encode(buffer.text().as_str()).unwrap()
+    }
+
+    #[test]
+    fn tabs_round_trip() {
+        let files: &[&[u8]] = &[
+            b"fn main() {\n\tlet a = 1;\n}\nx\ty\n",
+            b"\t\tdeep\n\t\tx\t\t\t\ty\n\n\t\n",
+            b"    spaces\n\t mixed\t\n"
+        ];
+        for file in files {
+            assert_eq!(round_trip(file, None), file.to_vec());
+            assert_eq!(round_trip(file, Some(Box::new(syntax::Rust::new()))), file.to_vec());
+        }
+    }
+
+    #[test]
+    fn formats_round_trip() {
+        let files: &[&[u8]] = &[
+            b"",
+            b"\n",
+            b"\n\n",
+            b"no final newline",
+            b"crlf\r\n\tline\r\n",
+            b"cr\rline\r",
+            b"\xef\xbb\xbfbom\n",
+            b"latin-1 \xe9t\xe9\n",
+            b"\xff\xfeu\x00t\x00f\x00\n\x00",
+            b"u\x00t\x00f\x00-\x001\x006\x00\n\x00"
+        ];
+        for file in files {
+            assert_eq!(round_trip(file, None), file.to_vec());
+        }
+    }
+
+    #[test]
+    fn leading_tabs_are_stored_wide() {
+        let mut buffer = Buffer::new(None);
+        buffer.set_tabs(2, false);
+        buffer.set_text("\ta\tb");
+        let chars: String = buffer.line(0).as_ref().unwrap().iter().flatten().map(|x| x.chr).collect();
+        assert_eq!(chars, "\t\ta\tb");
+        assert_eq!(buffer.text(), "\ta\tb");
+    }
+
+    #[test]
+    fn opposite_edits_undo() {
+        let mut buffer = Buffer::new(Some(Box::new(syntax::Rust::new())));
+        buffer.set_text("ab\ncd\n");
+        buffer.take_edits();
+
+        buffer.split_line(1, 0);
+        buffer.remove(0, 2);
+        buffer.join_line(1);
+        buffer.insert(0, 2, Char::new('x'));
+        buffer.remove_line(0);
+        let edits = buffer.take_edits();
+        assert_eq!(edits.len(), 5);
+        assert_eq!(buffer.text(), "bd\nx");
+
+        for edit in edits.iter().rev() {
+            buffer.apply(&edit.opposite());
+        }
+        assert_eq!(buffer.text(), "ab\ncd\n");
+        for edit in edits.iter() {
+            buffer.apply(edit);
+        }
+        assert_eq!(buffer.text(), "bd\nx");
+    }
+
+    #[test]
+    fn modified_follows_the_text() {
+        let mut buffer = Buffer::new(None);
+        buffer.set_text("ab");
+        buffer.mark_saved();
+        assert!(!buffer.modified());
+
+        buffer.insert(1, 0, Char::new('x'));
+        assert!(buffer.modified());
+        buffer.remove(1, 0);
+        assert!(!buffer.modified());
+
+        let mut format = buffer.format();
+        format.bom = !format.bom;
+        buffer.set_format(format);
+        assert!(buffer.modified());
+    }
+}
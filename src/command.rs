@@ -0,0 +1,144 @@
+use crate::format::Eol;
+use crate::prompt::{Ask, Prompt};
+use crate::view::{Align, Dir};
+use crate::JEdit;
+
+// an action that can be called by name
+pub struct Command {
+    pub name: &'static str,
+    pub run: fn(&mut JEdit)
+}
+
+pub const COMMANDS: &[Command] = &[
+    Command { name: "cursor.left", run: JEdit::cursor_left },
+    Command { name: "cursor.right", run: JEdit::cursor_right },
+    Command { name: "cursor.up", run: JEdit::cursor_up },
+    Command { name: "cursor.down", run: JEdit::cursor_down },
+    Command { name: "cursor.line-start", run: JEdit::line_start },
+    Command { name: "cursor.line-end", run: JEdit::line_end },
+    Command { name: "cursor.buffer-start", run: JEdit::buffer_start },
+    Command { name: "cursor.buffer-end", run: JEdit::buffer_end },
+
+    Command { name: "edit.newline", run: JEdit::newline },
+    Command { name: "edit.tab", run: JEdit::tab },
+    Command { name: "edit.backspace", run: JEdit::backspace },
+    Command { name: "edit.delete", run: JEdit::delete_forward },
+    Command { name: "edit.undo", run: JEdit::undo },
+    Command { name: "edit.redo", run: JEdit::redo },
+
+    Command { name: "mark.set", run: JEdit::set_mark },
+    Command { name: "mark.cancel", run: JEdit::cancel_mark },
+    Command { name: "region.kill", run: JEdit::kill_region },
+    Command { name: "region.copy", run: JEdit::copy_region },
+    Command { name: "kill.line", run: JEdit::kill_line },
+    Command { name: "kill.yank", run: JEdit::yank },
+    Command { name: "kill.yank-pop", run: JEdit::yank_pop },
+
+    Command { name: "buffer.open", run: |jedit| jedit.ask(Ask::Open, "open", "") },
+    Command { name: "buffer.save", run: JEdit::save },
+    Command { name: "buffer.close", run: JEdit::request_close },
+    Command { name: "buffer.next", run: |jedit| jedit.cycle(true) },
+    Command { name: "buffer.previous", run: |jedit| jedit.cycle(false) },
+    Command { name: "buffer.list", run: JEdit::list_buffers },
+    Command { name: "buffer.eol-lf", run: |jedit| jedit.convert(|x| x.eol = Eol::Lf) },
+    Command { name: "buffer.eol-crlf", run: |jedit| jedit.convert(|x| x.eol = Eol::CrLf) },
+    Command { name: "buffer.eol-cr", run: |jedit| jedit.convert(|x| x.eol = Eol::Cr) },
+    Command { name: "buffer.toggle-bom", run: |jedit| jedit.convert(|x| x.bom = !x.bom) },
+    Command { name: "buffer.toggle-readonly", run: JEdit::toggle_readonly },
+    Command { name: "buffer.reopen-encoding", run: |jedit| jedit.ask(Ask::Encoding, "encoding", "") },
+    Command { name: "buffer.toggle-final-newline", run: |jedit| jedit.convert(|x| x.final_newline = !x.final_newline) },
+
+    Command { name: "view.split-horizontal", run: |jedit| jedit.split(Dir::Horizontal) },
+    Command { name: "view.split-vertical", run: |jedit| jedit.split(Dir::Vertical) },
+    Command { name: "view.focus-next", run: JEdit::cycle_focus },
+    Command { name: "view.close", run: JEdit::unsplit },
+    Command { name: "view.toggle-wrap", run: JEdit::toggle_wrap },
+    Command { name: "view.center", run: |jedit| jedit.align(Align::Center) },
+    Command { name: "view.top", run: |jedit| jedit.align(Align::Top) },
+    Command { name: "view.bottom", run: |jedit| jedit.align(Align::Bottom) },
+    Command { name: "view.scroll-up", run: |jedit| jedit.scroll_by(-1) },
+    Command { name: "view.scroll-down", run: |jedit| jedit.scroll_by(1) },
+
+    Command { name: "goto.line", run: |jedit| jedit.ask(Ask::Line, "line", "") },
+    Command { name: "search.find", run: |jedit| jedit.ask(Ask::Search, "search", "") },
+    Command { name: "search.incremental", run: JEdit::isearch_start },
+    Command { name: "search.incremental-next", run: JEdit::isearch_next },
+    Command { name: "search.grep", run: |jedit| {
+        let word = jedit.word_at_cursor();
+        jedit.ask(Ask::Grep, "grep", word.as_str());
+    } },
+
+    Command { name: "listing.pick", run: JEdit::pick },
+
+    Command { name: "prompt.accept", run: JEdit::prompt_accept },
+    Command { name: "prompt.cancel", run: JEdit::prompt_cancel },
+    Command { name: "prompt.complete", run: JEdit::prompt_complete },
+    Command { name: "prompt.home", run: |jedit| jedit.with_prompt(Prompt::home) },
+    Command { name: "prompt.end", run: |jedit| jedit.with_prompt(Prompt::end) },
+    Command { name: "prompt.backspace", run: |jedit| jedit.with_prompt(Prompt::backspace) },
+    Command { name: "prompt.left", run: |jedit| jedit.with_prompt(Prompt::left) },
+    Command { name: "prompt.right", run: |jedit| jedit.with_prompt(Prompt::right) },
+    Command { name: "prompt.history-prev", run: |jedit| jedit.with_prompt(Prompt::history_prev) },
+    Command { name: "prompt.history-next", run: |jedit| jedit.with_prompt(Prompt::history_next) },
+    Command { name: "prompt.up", run: |jedit| jedit.prompt_move(true) },
+    Command { name: "prompt.down", run: |jedit| jedit.prompt_move(false) },
+
+    Command { name: "command.palette", run: |jedit| jedit.ask(Ask::Command, "command", "") },
+
+    Command { name: "keymap.emacs", run: |jedit| {
+        if let Err(err) = jedit.keymap.profile("emacs") {
+            jedit.message = Some(err);
+        }
+    } },
+    Command { name: "theme.select", run: |jedit| jedit.ask(Ask::Theme, "theme", "") },
+    Command { name: "editor.quit", run: JEdit::request_quit },
+
+    Command { name: "vim.toggle", run: JEdit::vim_toggle },
+    Command { name: "vim.normal", run: JEdit::vim_normal }
+];
+
+pub fn find(name: &str) -> Option<&'static Command> {
+    COMMANDS.iter().find(|x| x.name == name)
+}
+
+// None if query isn't a subsequence of name
+// higher is better
+fn score(query: &str, name: &str) -> Option<usize> {
+    let name: Vec<char> = name.chars().collect();
+
+    let mut score = 0;
+    let mut i = 0;
+    let mut last_maybe: Option<usize> = None;
+    for chr in query.chars() {
+        while i < name.len() && name[i] != chr {
+            i += 1;
+        }
+        if i == name.len() {
+            return None;
+        }
+
+        // runs and word starts count more
+        if last_maybe.map(|x| x + 1) == Some(i) {
+            score += 3;
+        } else if i == 0 || name[i - 1] == '.' || name[i - 1] == '-' {
+            score += 2;
+        } else {
+            score += 1;
+        }
+
+        last_maybe = Some(i);
+        i += 1;
+    }
+
+    Some(score)
+}
+
+// commands matching query best first
+pub fn fuzzy(query: &str) -> Vec<&'static Command> {
+    let mut scored: Vec<(usize, &'static Command)> = COMMANDS.iter()
+        .filter_map(|x| Some((score(query, x.name)?, x)))
+        .collect();
+    scored.sort_by(|a, b| b.0.cmp(&a.0).then(a.1.name.cmp(b.1.name)));
+
+    scored.into_iter().map(|(_, x)| x).collect()
+}
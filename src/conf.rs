@@ -0,0 +1,324 @@
+use std::env;
+use std::path::PathBuf;
+
+// small toml subset for config files
+// sections, dotted keys, strings, integers and booleans
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Value {
+    Str(String),
+    Int(i64),
+    Bool(bool)
+}
+
+impl Value {
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Value::Str(_) => "a string",
+            Value::Int(_) => "an integer",
+            Value::Bool(_) => "a boolean"
+        }
+    }
+}
+
+// one key = value line
+#[derive(Clone, Debug)]
+pub struct Entry {
+    // section followed by the key
+    pub path: Vec<String>,
+    pub value: Value,
+    // 1-based for error messages
+    pub line: usize
+}
+
+impl Entry {
+    pub fn str(&self) -> Result<&str, String> {
+        match &self.value {
+            Value::Str(string) => Ok(string),
+            value => Err(self.error(format!("expected a string, found {}", value.kind()).as_str()))
+        }
+    }
+
+    pub fn int(&self) -> Result<i64, String> {
+        match &self.value {
+            Value::Int(int) => Ok(*int),
+            value => Err(self.error(format!("expected an integer, found {}", value.kind()).as_str()))
+        }
+    }
+
+    pub fn bool(&self) -> Result<bool, String> {
+        match &self.value {
+            Value::Bool(bool) => Ok(*bool),
+            value => Err(self.error(format!("expected a boolean, found {}", value.kind()).as_str()))
+        }
+    }
+
+    pub fn key(&self) -> String {
+        self.path.join(".")
+    }
+
+    pub fn error(&self, message: &str) -> String {
+        format!("line {}: {}: {}", self.line, self.key(), message)
+    }
+}
+
+// $XDG_CONFIG_HOME/jedit or ~/.config/jedit
+pub fn dir() -> Option<PathBuf> {
+    if let Some(dir) = env::var_os("XDG_CONFIG_HOME") {
+        return Some(PathBuf::from(dir).join("jedit"));
+    }
+
+    let home = env::var_os("HOME").or_else(|| env::var_os("USERPROFILE"))?;
+    Some(PathBuf::from(home).join(".config").join("jedit"))
+}
+
+// $XDG_STATE_HOME/jedit or ~/.local/state/jedit
+pub fn state_dir() -> Option<PathBuf> {
+    if let Some(dir) = env::var_os("XDG_STATE_HOME") {
+        return Some(PathBuf::from(dir).join("jedit"));
+    }
+
+    let home = env::var_os("HOME").or_else(|| env::var_os("USERPROFILE"))?;
+    Some(PathBuf::from(home).join(".local").join("state").join("jedit"))
+}
+
+struct Parser {
+    chars: Vec<char>,
+    i: usize,
+    line: usize
+}
+
+impl Parser {
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.i).copied()
+    }
+
+    fn skip_space(&mut self) {
+        while self.peek() == Some(' ') || self.peek() == Some('\t') {
+            self.i += 1;
+        }
+    }
+
+    fn error(&self, message: &str) -> String {
+        format!("line {}: {}", self.line, message)
+    }
+
+    // bare or quoted keys separated by dots
+    fn key(&mut self) -> Result<Vec<String>, String> {
+        let mut path = Vec::new();
+        loop {
+            self.skip_space();
+            match self.peek() {
+                Some('"') | Some('\'') => path.push(self.string()?),
+                Some(chr) if chr.is_alphanumeric() || chr == '_' || chr == '-' => {
+                    let mut key = String::new();
+                    while let Some(chr) = self.peek() {
+                        if !(chr.is_alphanumeric() || chr == '_' || chr == '-') {
+                            break;
+                        }
+                        key.push(chr);
+                        self.i += 1;
+                    }
+                    path.push(key);
+                },
+                _ => return Err(self.error("expected a key"))
+            }
+
+            self.skip_space();
+            if self.peek() != Some('.') {
+                return Ok(path);
+            }
+            self.i += 1;
+        }
+    }
+
+    fn string(&mut self) -> Result<String, String> {
+        let quote = self.peek().unwrap();
+        self.i += 1;
+
+        let mut string = String::new();
+        loop {
+            let chr = match self.peek() {
+                Some('\n') | None => return Err(self.error("unterminated string")),
+                Some(chr) => chr
+            };
+            self.i += 1;
+
+            if chr == quote {
+                return Ok(string);
+            }
+
+            // single quoted strings are literal
+            if chr == '\\' && quote == '"' {
+                let escaped = self.peek().ok_or_else(|| self.error("unterminated string"))?;
+                self.i += 1;
+                string.push(match escaped {
+                    'n' => '\n',
+                    't' => '\t',
+                    'r' => '\r',
+                    '\\' => '\\',
+                    '"' => '"',
+                    _ => return Err(self.error(format!("unknown escape \\{}", escaped).as_str()))
+                });
+            } else {
+                string.push(chr);
+            }
+        }
+    }
+
+    fn value(&mut self) -> Result<Value, String> {
+        self.skip_space();
+        match self.peek() {
+            Some('"') | Some('\'') => Ok(Value::Str(self.string()?)),
+            _ => {
+                let mut word = String::new();
+                while let Some(chr) = self.peek() {
+                    if chr.is_whitespace() || chr == '#' {
+                        break;
+                    }
+                    word.push(chr);
+                    self.i += 1;
+                }
+
+                match word.as_str() {
+                    "true" => Ok(Value::Bool(true)),
+                    "false" => Ok(Value::Bool(false)),
+                    _ => match word.replace('_', "").parse::<i64>() {
+                        Ok(int) => Ok(Value::Int(int)),
+                        Err(_) => Err(self.error(format!("invalid value {}", word).as_str()))
+                    }
+                }
+            }
+        }
+    }
+
+    // nothing but a comment may follow
+    fn end_of_line(&mut self) -> Result<(), String> {
+        self.skip_space();
+        match self.peek() {
+            Some('#') => {
+                while self.peek().is_some() && self.peek() != Some('\n') {
+                    self.i += 1;
+                }
+                Ok(())
+            },
+            Some('\r') | Some('\n') | None => Ok(()),
+            Some(chr) => Err(self.error(format!("unexpected {}", chr).as_str()))
+        }
+    }
+}
+
+pub fn parse(text: &str) -> Result<Vec<Entry>, String> {
+    let mut parser = Parser {
+        chars: text.chars().collect(),
+        i: 0,
+        line: 1
+    };
+
+    let mut entries = Vec::new();
+    let mut section = Vec::new();
+    loop {
+        parser.skip_space();
+        match parser.peek() {
+            None => break,
+            Some('\n') => {
+                parser.i += 1;
+                parser.line += 1;
+                continue;
+            },
+            Some('\r') => {
+                parser.i += 1;
+                continue;
+            },
+            Some('#') => (),
+            Some('[') => {
+                parser.i += 1;
+                section = parser.key()?;
+                if parser.peek() != Some(']') {
+                    return Err(parser.error("expected ]"));
+                }
+                parser.i += 1;
+            },
+            Some(_) => {
+                let mut path = section.clone();
+                path.extend(parser.key()?);
+                if parser.peek() != Some('=') {
+                    return Err(parser.error("expected ="));
+                }
+                parser.i += 1;
+
+                let value = parser.value()?;
+                entries.push(Entry {
+                    path,
+                    value,
+                    line: parser.line
+                });
+            }
+        }
+
+        parser.end_of_line()?;
+    }
+
+    Ok(entries)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn paths(entries: &[Entry]) -> Vec<String> {
+        entries.iter().map(|x| x.key()).collect()
+    }
+
+    #[test]
+    fn values() {
+        let entries = parse("a = 1\nb = -2_000\nc = true\nd = false\ne = \"x\"\n").unwrap();
+        let values: Vec<Value> = entries.into_iter().map(|x| x.value).collect();
+        assert_eq!(values, vec![
+            Value::Int(1), Value::Int(-2000), Value::Bool(true), Value::Bool(false), Value::Str(String::from("x"))
+        ]);
+    }
+
+    #[test]
+    fn quoting() {
+        let entries = parse("a = \"tab\\there \\\"q\\\"\"\nb = 'c:\\dir'\n\"C-x C-s\" = 'x'\n").unwrap();
+        assert_eq!(entries[0].str(), Ok("tab\there \"q\""));
+        // single quotes are literal
+        assert_eq!(entries[1].str(), Ok("c:\\dir"));
+        assert_eq!(entries[2].path, vec![String::from("C-x C-s")]);
+    }
+
+    #[test]
+    fn sections() {
+        let text = "top = 1\n[mode.prompt]\nx = 1\n[filetype.\"c++\"]\na.b = 2 # comment\n";
+        let entries = parse(text).unwrap();
+        assert_eq!(paths(&entries), vec!["top", "mode.prompt.x", "filetype.c++.a.b"]);
+        assert_eq!(entries.iter().map(|x| x.line).collect::<Vec<usize>>(), vec![1, 3, 5]);
+    }
+
+    #[test]
+    fn comments_and_blank_lines() {
+        let entries = parse("# start\r\n\r\n  a = 1   # end\r\n").unwrap();
+        assert_eq!(paths(&entries), vec!["a"]);
+        assert_eq!(entries[0].line, 3);
+    }
+
+    #[test]
+    fn errors_have_line_numbers() {
+        assert_eq!(parse("a = 1\nb 2\n").unwrap_err(), "line 2: expected =");
+        assert_eq!(parse("\n\n[a\n").unwrap_err(), "line 3: expected ]");
+        assert_eq!(parse("a = \"open\nb = 1\n").unwrap_err(), "line 1: unterminated string");
+        assert_eq!(parse("a = \"\\q\"\n").unwrap_err(), "line 1: unknown escape \\q");
+        assert_eq!(parse("a = yes\n").unwrap_err(), "line 1: invalid value yes");
+        assert_eq!(parse("a = 1 2\n").unwrap_err(), "line 1: unexpected 2");
+        assert_eq!(parse("= 1\n").unwrap_err(), "line 1: expected a key");
+    }
+
+    #[test]
+    fn wrong_types() {
+        let entries = parse("\n[s]\nk = 1\n").unwrap();
+        assert_eq!(entries[0].str().unwrap_err(), "line 3: s.k: expected a string, found an integer");
+        assert_eq!(entries[0].int(), Ok(1));
+        assert!(entries[0].bool().is_err());
+    }
+}
@@ -0,0 +1,105 @@
+use jwin::{self, Win};
+
+use crate::key::Key;
+use crate::theme;
+
+// what the editor draws on and reads keys from
+// a jwin window or a terminal
+
+pub enum Event {
+    // new size in chars
+    Redraw(usize, usize),
+    Keys(Vec<Key>),
+    Close
+}
+
+pub trait Display {
+    // None when nothing happened
+    fn poll(&mut self) -> Option<Event>;
+
+    // one of the eight jwin palette colors
+    fn set_fg(&mut self, color: usize);
+    fn set_bg(&mut self, color: usize);
+    // "#rrggbb" colors of themes, displays
+    // without them draw the nearest palette color
+    fn set_fg_rgb(&mut self, r: u8, g: u8, b: u8) {
+        self.set_fg(theme::nearest(r, g, b));
+    }
+    fn set_bg_rgb(&mut self, r: u8, g: u8, b: u8) {
+        self.set_bg(theme::nearest(r, g, b));
+    }
+
+    // one of the theme::FONT_* values
+    fn set_font(&mut self, font: usize);
+    // lines through or under the next chars
+    // only the terminal has them, jwin fonts have no
+    // lines and windows draw the chars plain
+    fn set_decoration(&mut self, _underline: bool, _strikethrough: bool) {}
+
+    fn put_char(&mut self, x: usize, y: usize, chr: char);
+    fn put_str(&mut self, x: usize, y: usize, string: &str);
+
+    // the text cursor, drawn as a bar unless
+    // the display has a cursor of its own
+    fn put_cursor(&mut self, x: usize, y: usize) {
+        self.put_str(x, y, "|");
+    }
+
+    fn flush(&mut self);
+}
+
+impl Display for Win {
+    fn poll(&mut self) -> Option<Event> {
+        Some(match Win::poll(self)? {
+            jwin::Event::Redraw(w, h) => Event::Redraw(w, h),
+            jwin::Event::Key(code) => Event::Keys(Key::from_code(code)),
+            jwin::Event::Close => Event::Close,
+            #[allow(unreachable_patterns)]
+            _ => return None
+        })
+    }
+
+    fn set_fg(&mut self, color: usize) {
+        Win::set_fg(self, color);
+    }
+
+    fn set_bg(&mut self, color: usize) {
+        Win::set_bg(self, color);
+    }
+
+    fn set_font(&mut self, font: usize) {
+        Win::set_font(self, font);
+    }
+
+    fn put_char(&mut self, x: usize, y: usize, chr: char) {
+        Win::put_char(self, x, y, chr);
+    }
+
+    fn put_str(&mut self, x: usize, y: usize, string: &str) {
+        Win::put_str(self, x, y, string);
+    }
+
+    fn flush(&mut self) {
+        Win::flush(self);
+    }
+}
+
+// draws nothing, for tests of the editor
+#[cfg(test)]
+pub struct Headless;
+
+#[cfg(test)]
+impl Display for Headless {
+    fn poll(&mut self) -> Option<Event> {
+        None
+    }
+
+    fn set_fg(&mut self, _color: usize) {}
+    fn set_bg(&mut self, _color: usize) {}
+    fn set_font(&mut self, _font: usize) {}
+
+    fn put_char(&mut self, _x: usize, _y: usize, _chr: char) {}
+    fn put_str(&mut self, _x: usize, _y: usize, _string: &str) {}
+
+    fn flush(&mut self) {}
+}
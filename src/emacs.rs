@@ -0,0 +1,297 @@
+use std::cmp;
+
+use crate::prompt::Ask;
+use crate::view::{self, Pos};
+use crate::JEdit;
+
+// mark, kill ring and incremental search for the emacs profile
+
+// kills kept for yank-pop
+const KILL_RING_SIZE: usize = 30;
+
+pub struct Emacs {
+    // Some while a region is active
+    mark: Option<Pos>,
+
+    // newest kill last
+    kill_ring: Vec<String>,
+    // range and ring index of the last yank
+    // so yank-pop can replace it
+    yank: Option<(Pos, Pos, usize)>,
+
+    // where the cursor was when the search started
+    // and where the next match is looked for
+    isearch: Option<(Pos, Pos)>
+}
+
+impl Emacs {
+    pub fn new() -> Emacs {
+        Emacs {
+            mark: None,
+
+            kill_ring: Vec::new(),
+            yank: None,
+
+            isearch: None
+        }
+    }
+
+    // kills right after kills grow the newest entry
+    fn push_kill(&mut self, text: String, append: bool) {
+        match self.kill_ring.last_mut() {
+            Some(last) if append => last.push_str(text.as_str()),
+            _ => {
+                self.kill_ring.push(text);
+                if self.kill_ring.len() > KILL_RING_SIZE {
+                    self.kill_ring.remove(0);
+                }
+            }
+        }
+    }
+}
+
+impl JEdit {
+    fn cursor_pos(&self) -> Pos {
+        let cursor = self.view().cursor;
+        (cursor.x, cursor.y)
+    }
+
+    fn is_killing(&self) -> bool {
+        matches!(self.last_command.as_deref(), Some("kill.line") | Some("region.kill"))
+    }
+
+    pub(crate) fn line_start(&mut self) {
+        let y = self.view().cursor.y;
+        self.move_cursor(0, y);
+        self.view_mut().cursor.fake_x = 0;
+    }
+
+    pub(crate) fn line_end(&mut self) {
+        let y = self.view().cursor.y;
+        let x = self.buffer().line_len(y);
+        self.move_cursor(x, y);
+        self.view_mut().cursor.fake_x = x;
+    }
+
+    pub(crate) fn buffer_start(&mut self) {
+        self.move_cursor(0, 0);
+        self.view_mut().cursor.fake_x = 0;
+    }
+
+    pub(crate) fn buffer_end(&mut self) {
+        self.index_all();
+        let y = self.buffer().height().saturating_sub(1);
+        let x = self.buffer().line_len(y);
+        self.move_cursor(x, y);
+        self.view_mut().cursor.fake_x = x;
+    }
+
+    // removes the char under the cursor
+    pub(crate) fn delete_forward(&mut self) {
+        if self.listing().is_some() {
+            return;
+        }
+
+        let mut cursor = self.cursor_pos();
+        let leading = self.leading_tabs(cursor.1);
+        let end = if cursor.0 < leading {
+            // a group of leading tabs goes at once like with backspace
+            let tab_width = self.buffer().tab_width();
+            cursor.0 -= cursor.0 % tab_width;
+            (cmp::min(cursor.0 + tab_width, leading), cursor.1)
+        } else if cursor.0 < self.buffer().line_len(cursor.1) {
+            (cursor.0 + 1, cursor.1)
+        } else if cursor.1 + 1 < self.buffer().height() {
+            (0, cursor.1 + 1)
+        } else {
+            return;
+        };
+        self.delete_range(cursor, end);
+    }
+
+    pub(crate) fn set_mark(&mut self) {
+        self.emacs.mark = Some(self.cursor_pos());
+        self.message = Some(String::from("mark set"));
+    }
+
+    pub(crate) fn cancel_mark(&mut self) {
+        self.emacs.mark = None;
+        self.view_mut().selection = None;
+    }
+
+    // mark to cursor in buffer order
+    // the mark may be stale after edits
+    fn region(&self) -> Option<(Pos, Pos)> {
+        let mark = self.emacs.mark?;
+        let y = cmp::min(mark.1, self.buffer().height().saturating_sub(1));
+        let x = cmp::min(mark.0, self.buffer().line_len(y));
+
+        Some(view::ordered((x, y), self.cursor_pos()))
+    }
+
+    // shows the region like a selection
+    pub(crate) fn sync_region(&mut self) {
+        if self.emacs.mark.is_some() {
+            let region = self.region();
+            self.view_mut().selection = region;
+        }
+    }
+
+    pub(crate) fn kill_region(&mut self) {
+        if self.listing().is_some() {
+            return;
+        }
+
+        let (start, end) = match self.region() {
+            Some(region) => region,
+            None => {
+                self.message = Some(String::from("no mark set"));
+                return;
+            }
+        };
+        let text = self.delete_range(start, end);
+        let append = self.is_killing();
+        self.emacs.push_kill(text, append);
+        self.cancel_mark();
+    }
+
+    pub(crate) fn copy_region(&mut self) {
+        let (start, end) = match self.region() {
+            Some(region) => region,
+            None => {
+                self.message = Some(String::from("no mark set"));
+                return;
+            }
+        };
+        let text = self.text_range(start, end);
+        self.emacs.push_kill(text, false);
+        self.cancel_mark();
+    }
+
+    // kills to the end of the line
+    // or the newline on an empty rest
+    pub(crate) fn kill_line(&mut self) {
+        if self.listing().is_some() {
+            return;
+        }
+
+        let cursor = self.cursor_pos();
+        let len = self.buffer().line_len(cursor.1);
+        let end = if cursor.0 < len {
+            (len, cursor.1)
+        } else if cursor.1 + 1 < self.buffer().height() {
+            (0, cursor.1 + 1)
+        } else {
+            return;
+        };
+
+        let text = self.delete_range(cursor, end);
+        let append = self.is_killing();
+        self.emacs.push_kill(text, append);
+    }
+
+    pub(crate) fn yank(&mut self) {
+        if self.listing().is_some() || self.emacs.kill_ring.is_empty() {
+            return;
+        }
+
+        let i = self.emacs.kill_ring.len() - 1;
+        self.yank_at(i);
+    }
+
+    fn yank_at(&mut self, i: usize) {
+        let start = self.cursor_pos();
+        let text = self.emacs.kill_ring[i].clone();
+        self.insert_raw(text.as_str());
+        self.emacs.yank = Some((start, self.cursor_pos(), i));
+    }
+
+    // replaces the text just yanked with an older kill
+    pub(crate) fn yank_pop(&mut self) {
+        let yanked = matches!(self.last_command.as_deref(), Some("kill.yank") | Some("kill.yank-pop"));
+        let (start, end, i) = match self.emacs.yank {
+            Some(yank) if yanked => yank,
+            _ => {
+                self.message = Some(String::from("previous command was not a yank"));
+                return;
+            }
+        };
+
+        self.delete_range(start, end);
+        let len = self.emacs.kill_ring.len();
+        self.yank_at((i + len - 1) % len);
+    }
+
+    pub(crate) fn isearch_start(&mut self) {
+        let cursor = self.cursor_pos();
+        self.emacs.isearch = Some((cursor, cursor));
+        self.ask(Ask::ISearch, "i-search", "");
+    }
+
+    // the next match moves the start of the search
+    pub(crate) fn isearch_next(&mut self) {
+        let cursor = self.cursor_pos();
+        if let Some((_, from)) = self.emacs.isearch.as_mut() {
+            *from = (cursor.0 + 1, cursor.1);
+        }
+    }
+
+    // jumps to the first match of the prompt text after
+    // where the search started
+    pub(crate) fn isearch_update(&mut self) {
+        let (origin, from, text) = match (self.emacs.isearch, self.prompt.as_ref()) {
+            (Some((origin, from)), Some(prompt)) if prompt.ask == Ask::ISearch => (origin, from, prompt.text()),
+            _ => return
+        };
+
+        let found = if text.is_empty() {
+            Some(origin)
+        } else {
+            let current = self.current();
+            self.buffers[current].find(text.as_str(), from.0, from.1)
+        };
+        match found {
+            Some((x, y)) => {
+                self.move_cursor(x, y);
+                self.view_mut().cursor.fake_x = x;
+
+                let len = text.chars().count();
+                self.view_mut().selection = Some(((x, y), (x + len, y)));
+            },
+            None => self.view_mut().selection = None
+        }
+    }
+
+    // accepting leaves the cursor on the match
+    pub(crate) fn isearch_end(&mut self, cancelled: bool) {
+        if let Some((origin, _)) = self.emacs.isearch.take() {
+            if cancelled {
+                self.move_cursor(origin.0, origin.1);
+                self.view_mut().cursor.fake_x = origin.0;
+            }
+            self.view_mut().selection = None;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::tests::editor;
+
+    #[test]
+    fn delete_forward_takes_tab_groups() {
+        let mut jedit = editor("\t\tfoo\na\t\tb");
+        jedit.delete_forward();
+        assert_eq!(jedit.buffer().text(), "\tfoo\na\t\tb");
+        // from inside the group
+        jedit.move_cursor(2, 0);
+        jedit.delete_forward();
+        assert_eq!(jedit.buffer().text(), "foo\na\t\tb");
+        assert_eq!(jedit.view().cursor.x, 0);
+
+        // tabs after the indentation are single chars
+        jedit.move_cursor(1, 1);
+        jedit.delete_forward();
+        assert_eq!(jedit.buffer().text(), "foo\na\tb");
+    }
+}
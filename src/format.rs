@@ -0,0 +1,391 @@
+use std::fs;
+use std::io;
+use std::path::Path;
+
+// how the text of a file is laid out on disk
+// buffers only ever hold '\n' between lines
+
+const BOM: char = '\u{feff}';
+
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+pub enum Eol {
+    Lf,
+    CrLf,
+    Cr
+}
+
+impl Eol {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Eol::Lf => "\n",
+            Eol::CrLf => "\r\n",
+            Eol::Cr => "\r"
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Eol::Lf => "lf",
+            Eol::CrLf => "crlf",
+            Eol::Cr => "cr"
+        }
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+pub enum Encoding {
+    Utf8,
+    Utf16Le,
+    Utf16Be,
+    // every byte is the char with that number
+    // so any file decodes
+    Latin1
+}
+
+pub const ENCODINGS: &[Encoding] = &[Encoding::Utf8, Encoding::Utf16Le, Encoding::Utf16Be, Encoding::Latin1];
+
+impl Encoding {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Encoding::Utf8 => "utf-8",
+            Encoding::Utf16Le => "utf-16le",
+            Encoding::Utf16Be => "utf-16be",
+            Encoding::Latin1 => "latin-1"
+        }
+    }
+
+    pub fn by_name(name: &str) -> Option<Encoding> {
+        ENCODINGS.iter().find(|x| x.name() == name).copied()
+    }
+
+    // guess from the start of a file too big to read
+    // a char cut off at the end doesn't count
+    pub fn detect_start(start: &[u8]) -> Encoding {
+        // whole utf-16 units for byte order marks and nuls
+        match Encoding::detect(&start[..start.len() / 2 * 2]) {
+            Encoding::Utf8 | Encoding::Latin1 => (),
+            encoding => return encoding
+        }
+
+        match std::str::from_utf8(start) {
+            Err(err) if err.error_len().is_some() => Encoding::Latin1,
+            _ => Encoding::Utf8
+        }
+    }
+
+    // None if bytes aren't valid in this encoding
+    // a byte order mark stays in the text
+    pub fn decode(&self, bytes: &[u8]) -> Option<String> {
+        match self {
+            Encoding::Utf8 => String::from_utf8(bytes.to_vec()).ok(),
+            Encoding::Utf16Le | Encoding::Utf16Be => {
+                if bytes.len() % 2 == 1 {
+                    return None;
+                }
+                let units = bytes.chunks(2).map(|x| match self {
+                    Encoding::Utf16Le => u16::from_le_bytes([x[0], x[1]]),
+                    _ => u16::from_be_bytes([x[0], x[1]])
+                });
+                char::decode_utf16(units).collect::<Result<String, _>>().ok()
+            },
+            Encoding::Latin1 => Some(bytes.iter().map(|x| *x as char).collect())
+        }
+    }
+
+    pub fn encode(&self, text: &str) -> io::Result<Vec<u8>> {
+        match self {
+            Encoding::Utf8 => Ok(text.as_bytes().to_vec()),
+            Encoding::Utf16Le => Ok(text.encode_utf16().flat_map(|x| x.to_le_bytes()).collect()),
+            Encoding::Utf16Be => Ok(text.encode_utf16().flat_map(|x| x.to_be_bytes()).collect()),
+            Encoding::Latin1 => text.chars()
+                .map(|x| match x as u32 {
+                    0..=0xff => Ok(x as u8),
+                    _ => Err(io::Error::new(io::ErrorKind::InvalidData, format!("{:?} can't be saved as latin-1", x)))
+                })
+                .collect()
+        }
+    }
+
+    // byte order marks first, then nul bytes in every
+    // other byte for utf-16, then utf-8 and latin-1 last
+    fn detect(bytes: &[u8]) -> Encoding {
+        if bytes.starts_with(&[0xef, 0xbb, 0xbf]) {
+            return Encoding::Utf8;
+        }
+        if bytes.starts_with(&[0xff, 0xfe]) {
+            return Encoding::Utf16Le;
+        }
+        if bytes.starts_with(&[0xfe, 0xff]) {
+            return Encoding::Utf16Be;
+        }
+
+        // text files have no nuls so a few are enough
+        let pairs = bytes.len() / 2;
+        let even_nuls = bytes.iter().step_by(2).filter(|x| **x == 0).count();
+        let odd_nuls = bytes.iter().skip(1).step_by(2).filter(|x| **x == 0).count();
+        let utf16 = if odd_nuls * 4 >= pairs && even_nuls * 4 < odd_nuls {
+            Some(Encoding::Utf16Le)
+        } else if even_nuls * 4 >= pairs && odd_nuls * 4 < even_nuls {
+            Some(Encoding::Utf16Be)
+        } else {
+            None
+        };
+        if let Some(encoding) = utf16.filter(|x| pairs > 0 && x.decode(bytes).is_some()) {
+            return encoding;
+        }
+
+        if std::str::from_utf8(bytes).is_ok() {
+            Encoding::Utf8
+        } else {
+            Encoding::Latin1
+        }
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+pub struct Format {
+    pub encoding: Encoding,
+    pub eol: Eol,
+    // byte order mark at the start
+    pub bom: bool,
+    // the last line ends with eol
+    pub final_newline: bool,
+    // some lines end differently and
+    // saving gives them eol
+    pub mixed: bool
+}
+
+impl Format {
+    // for new files
+    pub fn new() -> Format {
+        Format {
+            encoding: Encoding::Utf8,
+            eol: Eol::Lf,
+            bom: false,
+            final_newline: true,
+            mixed: false
+        }
+    }
+
+    // the text of raw for a buffer and its format
+    // the first line ending decides the style
+    // and the others become it unless they are kept as chars
+    pub fn detect(raw: &str) -> (String, Format) {
+        let mut format = Format::new();
+
+        let raw = match raw.strip_prefix(BOM) {
+            Some(rest) => {
+                format.bom = true;
+                rest
+            },
+            None => raw
+        };
+
+        if let Some(i) = raw.find(['\r', '\n']) {
+            format.eol = match &raw[i..] {
+                rest if rest.starts_with("\r\n") => Eol::CrLf,
+                rest if rest.starts_with('\r') => Eol::Cr,
+                _ => Eol::Lf
+            };
+        }
+
+        // a cr of other line endings is kept as a char
+        // but a lone lf always breaks the line
+        format.mixed = match format.eol {
+            Eol::Lf => false,
+            Eol::CrLf => raw.matches('\n').count() != raw.matches("\r\n").count(),
+            Eol::Cr => raw.contains('\n')
+        };
+        let mut text = match format.eol {
+            Eol::Lf => String::from(raw),
+            eol => raw.replace(eol.as_str(), "\n")
+        };
+
+        if text.ends_with('\n') {
+            text.pop();
+        } else {
+            format.final_newline = false;
+        }
+
+        (text, format)
+    }
+
+    // text and format of bytes in the given encoding
+    // or a guessed one
+    pub fn decode(bytes: &[u8], encoding_maybe: Option<Encoding>) -> io::Result<(String, Format)> {
+        let encoding = encoding_maybe.unwrap_or_else(|| Encoding::detect(bytes));
+        let raw = encoding.decode(bytes).ok_or_else(|| {
+            io::Error::new(io::ErrorKind::InvalidData, format!("not valid {}", encoding.name()))
+        })?;
+
+        let (text, mut format) = Format::detect(raw.as_str());
+        format.encoding = encoding;
+        Ok((text, format))
+    }
+
+    // text of a buffer as it goes to disk
+    pub fn apply(&self, text: &str) -> String {
+        let mut raw = String::new();
+        if self.bom {
+            raw.push(BOM);
+        }
+
+        match self.eol {
+            Eol::Lf => raw.push_str(text),
+            eol => raw.push_str(text.replace('\n', eol.as_str()).as_str())
+        }
+        if self.final_newline {
+            raw.push_str(self.eol.as_str());
+        }
+
+        raw
+    }
+
+    pub fn encode(&self, text: &str) -> io::Result<Vec<u8>> {
+        self.encoding.encode(self.apply(text).as_str())
+    }
+
+    // for the status line, empty for the usual format
+    pub fn describe(&self) -> String {
+        let mut parts = Vec::new();
+        if self.encoding != Encoding::Utf8 {
+            parts.push(self.encoding.name());
+        }
+        if self.eol != Eol::Lf {
+            parts.push(self.eol.name());
+        }
+        if self.bom {
+            parts.push("bom");
+        }
+        if !self.final_newline {
+            parts.push("noeol");
+        }
+        if self.mixed {
+            parts.push("mixed");
+        }
+
+        parts.join(" ")
+    }
+}
+
+// text and format of the file at path
+pub fn read(path: &Path, encoding_maybe: Option<Encoding>) -> io::Result<(String, Format)> {
+    Format::decode(fs::read(path)?.as_slice(), encoding_maybe)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn utf16(text: &str, little: bool) -> Vec<u8> {
+        text.encode_utf16()
+            .flat_map(|x| if little {
+                x.to_le_bytes()
+            } else {
+                x.to_be_bytes()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn detects_utf16_without_bom() {
+        let (text, format) = Format::decode(utf16("hi there\n", true).as_slice(), None).unwrap();
+        assert_eq!((text.as_str(), format.encoding, format.bom), ("hi there", Encoding::Utf16Le, false));
+
+        let (text, format) = Format::decode(utf16("hi there\n", false).as_slice(), None).unwrap();
+        assert_eq!((text.as_str(), format.encoding, format.bom), ("hi there", Encoding::Utf16Be, false));
+    }
+
+    #[test]
+    fn detects_boms() {
+        let (text, format) = Format::decode(b"\xef\xbb\xbfa\n", None).unwrap();
+        assert_eq!((text.as_str(), format.encoding, format.bom), ("a", Encoding::Utf8, true));
+
+        let mut bytes = vec![0xff, 0xfe];
+        bytes.extend(utf16("a", true));
+        let (text, format) = Format::decode(bytes.as_slice(), None).unwrap();
+        assert_eq!((text.as_str(), format.encoding, format.bom), ("a", Encoding::Utf16Le, true));
+
+        let mut bytes = vec![0xfe, 0xff];
+        bytes.extend(utf16("a", false));
+        let (text, format) = Format::decode(bytes.as_slice(), None).unwrap();
+        assert_eq!((text.as_str(), format.encoding, format.bom), ("a", Encoding::Utf16Be, true));
+    }
+
+    #[test]
+    fn falls_back_to_latin1() {
+        let (text, format) = Format::decode(b"caf\xe9\n", None).unwrap();
+        assert_eq!((text.as_str(), format.encoding), ("caf\u{e9}", Encoding::Latin1));
+
+        // valid utf-8 stays utf-8
+        let (text, format) = Format::decode("caf\u{e9}\n".as_bytes(), None).unwrap();
+        assert_eq!((text.as_str(), format.encoding), ("caf\u{e9}", Encoding::Utf8));
+    }
+
+    #[test]
+    fn detects_from_the_start() {
+        // a char cut in two at the end
+        let start = &"abc\u{e9}".as_bytes()[..4];
+        assert_eq!(Encoding::detect_start(start), Encoding::Utf8);
+        assert_eq!(Encoding::detect_start("abc\u{e9}".as_bytes()), Encoding::Utf8);
+        assert_eq!(Encoding::detect_start(b"a\xe9b"), Encoding::Latin1);
+        assert_eq!(Encoding::detect_start(&utf16("a long line", true)[..9]), Encoding::Utf16Le);
+    }
+
+    #[test]
+    fn requested_encoding_wins() {
+        let (text, format) = Format::decode("\u{e9}".as_bytes(), Some(Encoding::Latin1)).unwrap();
+        assert_eq!((text.as_str(), format.encoding), ("\u{c3}\u{a9}", Encoding::Latin1));
+
+        assert!(Format::decode(b"\xe9", Some(Encoding::Utf8)).is_err());
+        assert!(Format::decode(b"abc", Some(Encoding::Utf16Le)).is_err());
+    }
+
+    #[test]
+    fn detects_line_endings() {
+        let cases: &[(&str, &str, Eol, bool, bool)] = &[
+            ("a\nb\n", "a\nb", Eol::Lf, true, false),
+            ("a\r\nb\r\n", "a\nb", Eol::CrLf, true, false),
+            ("a\rb", "a\nb", Eol::Cr, false, false),
+            // the first ending counts and other crs stay
+            ("a\nb\r\nc", "a\nb\r\nc", Eol::Lf, false, false),
+            ("a\r\nb\rc\r\n", "a\nb\rc", Eol::CrLf, true, false),
+            // lone lfs can't stay so saving changes them
+            ("a\r\nb\nc", "a\nb\nc", Eol::CrLf, false, true),
+            ("a\rb\r\n", "a\nb\n", Eol::Cr, true, true),
+            ("", "", Eol::Lf, false, false)
+        ];
+        for (raw, text, eol, final_newline, mixed) in cases {
+            let (decoded, format) = Format::detect(raw);
+            assert_eq!(
+                (decoded.as_str(), format.eol, format.final_newline, format.mixed),
+                (*text, *eol, *final_newline, *mixed)
+            );
+        }
+    }
+
+    #[test]
+    fn encodes_back_to_the_same_bytes() {
+        let mut utf16_bom = vec![0xfe, 0xff];
+        utf16_bom.extend(utf16("x\r\ny\r\n", false));
+        let files: Vec<Vec<u8>> = vec![
+            b"plain\n".to_vec(),
+            b"\xef\xbb\xbfbom\r\nno final".to_vec(),
+            b"caf\xe9\rold mac\r".to_vec(),
+            utf16("le without bom\n", true),
+            utf16_bom
+        ];
+        for bytes in files {
+            let (text, format) = Format::decode(bytes.as_slice(), None).unwrap();
+            assert_eq!(format.encode(text.as_str()).unwrap(), bytes);
+        }
+    }
+
+    #[test]
+    fn latin1_refuses_wide_chars() {
+        let mut format = Format::new();
+        format.encoding = Encoding::Latin1;
+        assert_eq!(format.encode("\u{ff}").unwrap(), vec![0xff, b'\n']);
+        assert!(format.encode("\u{100}").is_err());
+    }
+}
@@ -0,0 +1,292 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+// project wide grep
+// walks the tree and collects
+// file:line matches
+
+#[derive(Clone, Debug)]
+pub struct Match {
+    pub path: PathBuf,
+    pub line: usize,
+    pub col: usize,
+    pub text: String
+}
+
+// one .gitignore line
+struct Rule {
+    glob: String,
+    negate: bool,
+    dir_only: bool,
+    // pattern contains a slash so it is
+    // matched against the whole relative path
+    anchored: bool
+}
+
+// rules of a single .gitignore with
+// the directory it lives in
+struct Ignore {
+    base: PathBuf,
+    rules: Vec<Rule>
+}
+
+impl Ignore {
+    fn load(dir: &Path) -> Option<Ignore> {
+        let string = fs::read_to_string(dir.join(".gitignore")).ok()?;
+        Some(Ignore::parse(dir, string.as_str()))
+    }
+
+    // string is the .gitignore in base
+    fn parse(base: &Path, string: &str) -> Ignore {
+        let mut rules = Vec::new();
+        for line in string.lines() {
+            let mut line = line.trim_end();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let negate = line.starts_with('!');
+            if negate {
+                line = &line[1..];
+            }
+            let dir_only = line.ends_with('/');
+            if dir_only {
+                line = &line[..line.len() - 1];
+            }
+            let anchored = line.contains('/');
+            let glob = line.trim_start_matches('/');
+
+            rules.push(Rule {
+                glob: String::from(glob),
+                negate, dir_only, anchored
+            });
+        }
+
+        Ignore {
+            base: base.to_path_buf(),
+            rules
+        }
+    }
+
+    // None if no rule applies
+    fn ignored(&self, path: &Path, is_dir: bool) -> Option<bool> {
+        let rel = path.strip_prefix(&self.base).ok()?.to_string_lossy().replace('\\', "/");
+        let name = path.file_name()?.to_string_lossy();
+
+        let mut res = None;
+        // last matching rule wins
+        for rule in self.rules.iter() {
+            if rule.dir_only && !is_dir {
+                continue;
+            }
+
+            let hit = if rule.anchored {
+                glob(&rule.glob, &rel)
+            } else {
+                glob(&rule.glob, &name)
+            };
+            if hit {
+                res = Some(!rule.negate);
+            }
+        }
+
+        res
+    }
+}
+
+// supports * ? and **
+fn glob(pattern: &str, string: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let string: Vec<char> = string.chars().collect();
+
+    glob_at(&pattern, &string)
+}
+
+fn glob_at(pattern: &[char], string: &[char]) -> bool {
+    match pattern.first() {
+        None => string.is_empty(),
+        Some('*') => {
+            // ** also crosses slashes
+            let deep = pattern.get(1) == Some(&'*');
+            let rest = if deep {
+                &pattern[2..]
+            } else {
+                &pattern[1..]
+            };
+
+            for i in 0..=string.len() {
+                if glob_at(rest, &string[i..]) {
+                    return true;
+                }
+                if i < string.len() && string[i] == '/' && !deep {
+                    break;
+                }
+            }
+
+            false
+        },
+        Some('?') => !string.is_empty() && string[0] != '/' && glob_at(&pattern[1..], &string[1..]),
+        Some(chr) => !string.is_empty() && string[0] == *chr && glob_at(&pattern[1..], &string[1..])
+    }
+}
+
+fn walk(dir: &Path, ignores: &mut Vec<Ignore>, pattern: &str, matches: &mut Vec<Match>) {
+    let pushed = match Ignore::load(dir) {
+        Some(ignore) => {
+            ignores.push(ignore);
+            true
+        },
+        None => false
+    };
+
+    let entries_maybe = fs::read_dir(dir);
+    if let Ok(entries) = entries_maybe {
+        let mut paths: Vec<(PathBuf, fs::FileType)> = entries.filter_map(|x| x.ok())
+            .filter_map(|x| Some((x.path(), x.file_type().ok()?)))
+            .collect();
+        // stable result order
+        paths.sort_by(|a, b| a.0.cmp(&b.0));
+
+        for (path, file_type) in paths {
+            if path.ends_with(".git") {
+                continue;
+            }
+            // linked dirs can loop back up the tree
+            // linked files are searched
+            if file_type.is_symlink() && path.is_dir() {
+                continue;
+            }
+
+            let is_dir = file_type.is_dir();
+
+            // deeper .gitignore files override outer ones
+            let mut ignored = false;
+            for ignore in ignores.iter() {
+                if let Some(res) = ignore.ignored(&path, is_dir) {
+                    ignored = res;
+                }
+            }
+            if ignored {
+                continue;
+            }
+
+            if is_dir {
+                walk(&path, ignores, pattern, matches);
+            } else {
+                search_file(&path, pattern, matches);
+            }
+        }
+    }
+
+    if pushed {
+        ignores.pop();
+    }
+}
+
+fn search_file(path: &Path, pattern: &str, matches: &mut Vec<Match>) {
+    // skips binary and non utf-8 files
+    let string = match fs::read_to_string(path) {
+        Ok(string) => string,
+        Err(_) => return
+    };
+    if string.contains('\0') {
+        return;
+    }
+
+    // every match on a line gets its own entry
+    for (y, line) in string.lines().enumerate() {
+        for (i, _) in line.match_indices(pattern) {
+            matches.push(Match {
+                path: path.to_path_buf(),
+                line: y,
+                col: line[..i].chars().count(),
+                text: String::from(line.trim())
+            });
+        }
+    }
+}
+
+// literal search from root
+pub fn grep(root: &Path, pattern: &str) -> Vec<Match> {
+    let mut matches = Vec::new();
+    if pattern.is_empty() {
+        return matches;
+    }
+
+    walk(root, &mut Vec::new(), pattern, &mut matches);
+
+    matches
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rules(string: &str) -> Ignore {
+        Ignore::parse(Path::new("/p"), string)
+    }
+
+    #[test]
+    fn globs() {
+        assert!(glob("*.rs", "main.rs"));
+        assert!(!glob("*.rs", "main.rsx"));
+        assert!(glob("a?c", "abc"));
+        assert!(!glob("a?c", "a/c"));
+        assert!(!glob("a/*.rs", "a/b/c.rs"));
+        assert!(glob("a/**/c.rs", "a/b/x/c.rs"));
+        assert!(glob("**/c.rs", "a/b/c.rs"));
+    }
+
+    #[test]
+    fn names_match_at_any_depth() {
+        let ignore = rules("# comment\n\ntarget\n*.log\n");
+        assert_eq!(ignore.ignored(Path::new("/p/target"), true), Some(true));
+        assert_eq!(ignore.ignored(Path::new("/p/a/b/target"), false), Some(true));
+        assert_eq!(ignore.ignored(Path::new("/p/a/x.log"), false), Some(true));
+        assert_eq!(ignore.ignored(Path::new("/p/a/x.rs"), false), None);
+        // outside the dir of the .gitignore
+        assert_eq!(ignore.ignored(Path::new("/q/target"), true), None);
+    }
+
+    #[test]
+    fn dir_only_rules() {
+        let ignore = rules("build/\n");
+        assert_eq!(ignore.ignored(Path::new("/p/build"), true), Some(true));
+        assert_eq!(ignore.ignored(Path::new("/p/build"), false), None);
+    }
+
+    #[test]
+    fn anchored_rules() {
+        let ignore = rules("/out\ndocs/*.html\n");
+        assert_eq!(ignore.ignored(Path::new("/p/out"), true), Some(true));
+        assert_eq!(ignore.ignored(Path::new("/p/src/out"), true), None);
+        assert_eq!(ignore.ignored(Path::new("/p/docs/a.html"), false), Some(true));
+        assert_eq!(ignore.ignored(Path::new("/p/docs/api/a.html"), false), None);
+    }
+
+    #[test]
+    fn last_rule_wins() {
+        let ignore = rules("*.log\n!keep.log\n");
+        assert_eq!(ignore.ignored(Path::new("/p/x.log"), false), Some(true));
+        assert_eq!(ignore.ignored(Path::new("/p/keep.log"), false), Some(false));
+
+        let ignore = rules("!keep.log\n*.log\n");
+        assert_eq!(ignore.ignored(Path::new("/p/keep.log"), false), Some(true));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn walks_without_following_linked_dirs() {
+        let root = std::env::temp_dir().join(format!("jedit-grep-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(root.join("sub")).unwrap();
+        fs::write(root.join("sub/a.txt"), "x x\nno\nx\n").unwrap();
+        // a loop back to the root
+        std::os::unix::fs::symlink(&root, root.join("sub/up")).unwrap();
+
+        let matches = grep(&root, "x");
+        let _ = fs::remove_dir_all(&root);
+        let found: Vec<(usize, usize)> = matches.iter().map(|x| (x.line, x.col)).collect();
+        assert_eq!(found, vec![(0, 0), (0, 2), (2, 0)]);
+    }
+}
@@ -0,0 +1,87 @@
+use jwin::Code;
+
+// a single key press
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Key {
+    Char(char),
+    // lowercase letters or space
+    Ctrl(char),
+    Enter, Tab, Esc, Backspace,
+    Left, Right, Up, Down,
+    // only the terminal reports these, jwin has no
+    // modifiers on arrows so bindings need a fallback
+    CtrlUp, CtrlDown
+}
+
+impl Key {
+    // jwin sends control keys as control chars
+    pub fn from_char(chr: char) -> Option<Key> {
+        Some(match chr {
+            '\n' | '\r' => Key::Enter,
+            '\t' => Key::Tab,
+            '\x1b' => Key::Esc,
+            '\0' => Key::Ctrl(' '),
+            '\x01'..='\x1a' => Key::Ctrl((chr as u8 - 1 + b'a') as char),
+            _ if chr.is_control() => return None,
+            _ => Key::Char(chr)
+        })
+    }
+
+    pub fn from_code(code: Code) -> Vec<Key> {
+        match code {
+            Code::Showable(string) => string.chars().filter_map(Key::from_char).collect(),
+            Code::Backspace => vec![Key::Backspace],
+            Code::Left => vec![Key::Left],
+            Code::Right => vec![Key::Right],
+            Code::Up => vec![Key::Up],
+            Code::Down => vec![Key::Down],
+            #[allow(unreachable_patterns)]
+            _ => Vec::new()
+        }
+    }
+
+    // inverse of name
+    pub fn parse(string: &str) -> Option<Key> {
+        Some(match string {
+            "Enter" => Key::Enter,
+            "Tab" => Key::Tab,
+            "Esc" => Key::Esc,
+            "Backspace" => Key::Backspace,
+            "Left" => Key::Left,
+            "Right" => Key::Right,
+            "Up" => Key::Up,
+            "Down" => Key::Down,
+            "C-Up" => Key::CtrlUp,
+            "C-Down" => Key::CtrlDown,
+            "Space" => Key::Char(' '),
+            "C-Space" => Key::Ctrl(' '),
+            _ => {
+                let mut chars = string.chars();
+                match (chars.next()?, chars.next(), chars.next(), chars.next()) {
+                    (chr, None, None, None) => Key::Char(chr),
+                    ('C', Some('-'), Some(chr), None) if chr.is_ascii_lowercase() => Key::Ctrl(chr),
+                    _ => return None
+                }
+            }
+        })
+    }
+
+    pub fn name(&self) -> String {
+        match self {
+            Key::Char(' ') => String::from("Space"),
+            Key::Char(chr) => chr.to_string(),
+            Key::Ctrl(' ') => String::from("C-Space"),
+            Key::Ctrl(chr) => format!("C-{}", chr),
+            Key::Enter => String::from("Enter"),
+            Key::Tab => String::from("Tab"),
+            Key::Esc => String::from("Esc"),
+            Key::Backspace => String::from("Backspace"),
+            Key::Left => String::from("Left"),
+            Key::Right => String::from("Right"),
+            Key::Up => String::from("Up"),
+            Key::Down => String::from("Down"),
+            Key::CtrlUp => String::from("C-Up"),
+            Key::CtrlDown => String::from("C-Down")
+        }
+    }
+}
@@ -0,0 +1,424 @@
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use crate::command;
+use crate::conf;
+use crate::key::Key;
+
+// mode, keys, command
+type Bindings = &'static [(&'static str, &'static str, &'static str)];
+
+// filetype independent defaults
+const DEFAULT: Bindings = &[
+    ("edit", "Left", "cursor.left"),
+    ("edit", "Right", "cursor.right"),
+    ("edit", "Up", "cursor.up"),
+    ("edit", "Down", "cursor.down"),
+
+    ("edit", "Enter", "edit.newline"),
+    ("edit", "Tab", "edit.tab"),
+    ("edit", "Backspace", "edit.backspace"),
+    ("edit", "C-z", "edit.undo"),
+    ("edit", "C-y", "edit.redo"),
+
+    ("edit", "C-e", "buffer.open"),
+    ("edit", "C-s", "buffer.save"),
+    ("edit", "C-w", "buffer.close"),
+    ("edit", "C-n", "buffer.next"),
+    ("edit", "C-p", "buffer.previous"),
+    ("edit", "C-b", "buffer.list"),
+
+    ("edit", "C-t", "view.split-horizontal"),
+    ("edit", "C-v", "view.split-vertical"),
+    ("edit", "C-o", "view.focus-next"),
+    ("edit", "C-k", "view.close"),
+
+    ("edit", "C-l", "goto.line"),
+    ("edit", "C-f", "search.find"),
+    ("edit", "C-g", "search.grep"),
+
+    ("edit", "C-r", "command.palette"),
+
+    ("edit", "C-u", "view.scroll-up"),
+    ("edit", "C-d", "view.scroll-down"),
+    ("listing", "C-u", "view.scroll-up"),
+    ("listing", "C-d", "view.scroll-down"),
+    // only the terminal sends these
+    ("edit", "C-Up", "view.scroll-up"),
+    ("edit", "C-Down", "view.scroll-down"),
+    ("listing", "C-Up", "view.scroll-up"),
+    ("listing", "C-Down", "view.scroll-down"),
+
+    // listings are moved through like buffers
+    ("listing", "Left", "cursor.left"),
+    ("listing", "Right", "cursor.right"),
+    ("listing", "Up", "cursor.up"),
+    ("listing", "Down", "cursor.down"),
+    ("listing", "Enter", "listing.pick"),
+    ("listing", "C-w", "buffer.close"),
+    ("listing", "C-n", "buffer.next"),
+    ("listing", "C-p", "buffer.previous"),
+    ("listing", "C-b", "buffer.list"),
+    ("listing", "C-o", "view.focus-next"),
+    ("listing", "C-k", "view.close"),
+    ("listing", "C-r", "command.palette"),
+
+    ("prompt", "Enter", "prompt.accept"),
+    ("prompt", "Esc", "prompt.cancel"),
+    ("prompt", "C-g", "prompt.cancel"),
+    ("prompt", "Tab", "prompt.complete"),
+    ("prompt", "C-a", "prompt.home"),
+    ("prompt", "C-e", "prompt.end"),
+    ("prompt", "Backspace", "prompt.backspace"),
+    ("prompt", "Left", "prompt.left"),
+    ("prompt", "Right", "prompt.right"),
+    ("prompt", "Up", "prompt.up"),
+    ("prompt", "Down", "prompt.down"),
+
+    // vim modes, unbound keys go to the vim layer
+    // and insert mode falls back to edit bindings
+    ("normal", "Left", "cursor.left"),
+    ("normal", "Right", "cursor.right"),
+    ("normal", "Up", "cursor.up"),
+    ("normal", "Down", "cursor.down"),
+    ("normal", "C-r", "command.palette"),
+    ("normal", "C-e", "view.scroll-down"),
+    ("normal", "C-y", "view.scroll-up"),
+    ("normal", "C-Up", "view.scroll-up"),
+    ("normal", "C-Down", "view.scroll-down"),
+    ("visual", "Left", "cursor.left"),
+    ("visual", "Right", "cursor.right"),
+    ("visual", "Up", "cursor.up"),
+    ("visual", "Down", "cursor.down"),
+    ("insert", "Esc", "vim.normal")
+];
+
+// bindings layered over the defaults
+const EMACS: Bindings = &[
+    ("edit", "C-a", "cursor.line-start"),
+    ("edit", "C-e", "cursor.line-end"),
+    ("edit", "C-f", "cursor.right"),
+    ("edit", "C-b", "cursor.left"),
+    ("edit", "C-n", "cursor.down"),
+    ("edit", "C-p", "cursor.up"),
+    ("edit", "Esc <", "cursor.buffer-start"),
+    ("edit", "Esc >", "cursor.buffer-end"),
+    ("edit", "C-d", "edit.delete"),
+
+    ("edit", "C-Space", "mark.set"),
+    ("edit", "C-g", "mark.cancel"),
+    ("edit", "C-w", "region.kill"),
+    ("edit", "Esc w", "region.copy"),
+    ("edit", "C-k", "kill.line"),
+    ("edit", "C-y", "kill.yank"),
+    ("edit", "Esc y", "kill.yank-pop"),
+
+    ("edit", "C-s", "search.incremental"),
+    ("prompt", "C-s", "search.incremental-next"),
+    ("edit", "Esc g g", "goto.line"),
+
+    // terminals send alt as a leading esc
+    ("edit", "Esc x", "command.palette"),
+    ("listing", "Esc x", "command.palette"),
+
+    ("edit", "C-x C-f", "buffer.open"),
+    ("edit", "C-x C-s", "buffer.save"),
+    ("edit", "C-x k", "buffer.close"),
+    ("edit", "C-x b", "buffer.list"),
+    ("edit", "C-x Right", "buffer.next"),
+    ("edit", "C-x Left", "buffer.previous"),
+    ("edit", "C-x 2", "view.split-horizontal"),
+    ("edit", "C-x 3", "view.split-vertical"),
+    ("edit", "C-x o", "view.focus-next"),
+    ("edit", "C-x 0", "view.close"),
+    ("edit", "C-x u", "edit.undo"),
+    ("edit", "C-x C-c", "editor.quit"),
+
+    // the defaults use these as single keys
+    ("edit", "C-t", UNBOUND),
+    ("edit", "C-v", "view.scroll-down"),
+    ("edit", "Esc v", "view.scroll-up"),
+    ("edit", "C-o", UNBOUND),
+    ("edit", "C-l", "view.center"),
+    ("edit", "C-r", UNBOUND)
+];
+
+// name, bindings
+const PROFILES: &[(&str, Bindings)] = &[
+    ("emacs", EMACS)
+];
+
+// bound to a command to unbind a key
+pub const UNBOUND: &str = "none";
+
+struct Binding {
+    keys: Vec<Key>,
+    command: String,
+
+    mode: String,
+    // None applies to every filetype
+    filetype: Option<String>
+}
+
+impl Binding {
+    fn applies(&self, mode: &str, filetype: &str) -> bool {
+        self.mode == mode && self.filetype.as_deref().unwrap_or(filetype) == filetype
+    }
+
+    // filetype overrides win over general bindings
+    fn specificity(&self) -> usize {
+        self.filetype.is_some() as usize
+    }
+}
+
+pub enum Lookup {
+    Command(String),
+    // more keys are needed
+    Prefix,
+    None
+}
+
+// "C-x C-s" to keys
+pub fn parse_keys(string: &str) -> Option<Vec<Key>> {
+    let keys: Option<Vec<Key>> = string.split_whitespace().map(Key::parse).collect();
+    match keys {
+        Some(keys) if !keys.is_empty() => Some(keys),
+        _ => None
+    }
+}
+
+pub fn keys_name(keys: &[Key]) -> String {
+    keys.iter().map(|x| x.name()).collect::<Vec<String>>().join(" ")
+}
+
+pub struct Keymap {
+    // later bindings override earlier ones
+    bindings: Vec<Binding>
+}
+
+impl Keymap {
+    pub fn new() -> Keymap {
+        let mut keymap = Keymap {
+            bindings: Vec::new()
+        };
+
+        for (mode, keys, command) in DEFAULT {
+            keymap.bind(parse_keys(keys).unwrap(), command, mode, None);
+        }
+
+        keymap
+    }
+
+    // adds the bindings of a named profile
+    pub fn profile(&mut self, name: &str) -> Result<(), String> {
+        let (_, bindings) = PROFILES.iter()
+            .find(|(x, _)| *x == name)
+            .ok_or_else(|| format!("unknown keymap {}", name))?;
+        for (mode, keys, command) in bindings.iter() {
+            self.bind(parse_keys(keys).unwrap(), command, mode, None);
+        }
+
+        Ok(())
+    }
+
+    pub fn bind(&mut self, keys: Vec<Key>, command: &str, mode: &str, filetype: Option<&str>) {
+        self.bindings.push(Binding {
+            keys,
+            command: String::from(command),
+
+            mode: String::from(mode),
+            filetype: filetype.map(String::from)
+        });
+    }
+
+    // the winning binding for exactly these keys
+    fn find(&self, keys: &[Key], mode: &str, filetype: &str) -> Option<&Binding> {
+        let mut best: Option<&Binding> = None;
+        for binding in self.bindings.iter() {
+            if binding.keys != keys || !binding.applies(mode, filetype) {
+                continue;
+            }
+            match best {
+                Some(x) if x.specificity() > binding.specificity() => (),
+                _ => best = Some(binding)
+            }
+        }
+
+        best
+    }
+
+    pub fn lookup(&self, keys: &[Key], mode: &str, filetype: &str) -> Lookup {
+        if let Some(binding) = self.find(keys, mode, filetype) {
+            if binding.command != UNBOUND {
+                return Lookup::Command(binding.command.clone());
+            }
+        }
+
+        let prefix = self.bindings.iter().any(|x| {
+            x.keys.len() > keys.len() && x.keys.starts_with(keys) && x.applies(mode, filetype)
+                && self.find(&x.keys, mode, filetype).is_some_and(|x| x.command != UNBOUND)
+        });
+        if prefix {
+            Lookup::Prefix
+        } else {
+            Lookup::None
+        }
+    }
+
+    // key sequences that currently run command
+    // modes are tried in order like insert then edit
+    pub fn keys(&self, command: &str, modes: &[&str], filetype: &str) -> Vec<String> {
+        let mut names = Vec::new();
+        for (i, mode) in modes.iter().enumerate() {
+            for binding in self.bindings.iter() {
+                if binding.command != command || !binding.applies(mode, filetype) {
+                    continue;
+                }
+
+                // skip bindings that are overridden
+                // or taken by an earlier mode
+                let winner = self.find(&binding.keys, mode, filetype).unwrap();
+                let taken = modes[..i].iter().any(|x| !matches!(self.lookup(&binding.keys, x, filetype), Lookup::None));
+                let name = keys_name(&binding.keys);
+                if winner.command == command && !taken && !names.contains(&name) {
+                    names.push(name);
+                }
+            }
+        }
+
+        names
+    }
+
+    // sections are [mode.<name>], [filetype.<name>]
+    // or both like [filetype.rust.mode.prompt]
+    // keys outside of a mode section are for editing
+    pub fn load_str(&mut self, text: &str) -> Result<(), String> {
+        for entry in conf::parse(text)? {
+            let (keys_string, section) = entry.path.split_last().unwrap();
+
+            let mut mode = "edit";
+            let mut filetype = None;
+            for pair in section.chunks(2) {
+                match pair {
+                    [kind, name] if kind == "mode" => mode = name.as_str(),
+                    [kind, name] if kind == "filetype" => filetype = Some(name.as_str()),
+                    _ => return Err(entry.error("sections are mode.<name> or filetype.<name>"))
+                }
+            }
+
+            let keys = parse_keys(keys_string).ok_or_else(|| entry.error("invalid keys"))?;
+            let command = entry.str()?;
+            if command != UNBOUND && command::find(command).is_none() {
+                return Err(entry.error(format!("unknown command {}", command).as_str()));
+            }
+            self.bind(keys, command, mode, filetype);
+        }
+
+        Ok(())
+    }
+
+    // a missing file isn't an error
+    pub fn load(&mut self, path: &Path) -> Result<(), String> {
+        match fs::read_to_string(path) {
+            Ok(text) => self.load_str(text.as_str()).map_err(|x| format!("{}: {}", path.display(), x)),
+            Err(ref err) if err.kind() == io::ErrorKind::NotFound => Ok(()),
+            Err(err) => Err(format!("{}: {}", path.display(), err))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn command(keymap: &Keymap, keys: &str, mode: &str, filetype: &str) -> Option<String> {
+        match keymap.lookup(&parse_keys(keys).unwrap(), mode, filetype) {
+            Lookup::Command(command) => Some(command),
+            _ => None
+        }
+    }
+
+    fn is_prefix(keymap: &Keymap, keys: &str, mode: &str) -> bool {
+        matches!(keymap.lookup(&parse_keys(keys).unwrap(), mode, "text"), Lookup::Prefix)
+    }
+
+    #[test]
+    fn filetype_wins_over_mode() {
+        let mut keymap = Keymap::new();
+        keymap.load_str("[filetype.rust]\nC-s = \"goto.line\"\n").unwrap();
+        assert_eq!(command(&keymap, "C-s", "edit", "rust").as_deref(), Some("goto.line"));
+        assert_eq!(command(&keymap, "C-s", "edit", "text").as_deref(), Some("buffer.save"));
+
+        // a later general binding doesn't beat the filetype one
+        keymap.load_str("C-s = \"view.center\"\n").unwrap();
+        assert_eq!(command(&keymap, "C-s", "edit", "rust").as_deref(), Some("goto.line"));
+        assert_eq!(command(&keymap, "C-s", "edit", "text").as_deref(), Some("view.center"));
+    }
+
+    #[test]
+    fn modes_are_separate() {
+        let mut keymap = Keymap::new();
+        keymap.load_str("[filetype.rust.mode.prompt]\nC-s = \"prompt.end\"\n").unwrap();
+        assert_eq!(command(&keymap, "C-s", "prompt", "rust").as_deref(), Some("prompt.end"));
+        assert_eq!(command(&keymap, "C-s", "edit", "rust").as_deref(), Some("buffer.save"));
+        assert_eq!(command(&keymap, "C-s", "prompt", "text"), None);
+    }
+
+    #[test]
+    fn prefix_and_full_match() {
+        let mut keymap = Keymap::new();
+        keymap.profile("emacs").unwrap();
+        assert!(is_prefix(&keymap, "C-x", "edit"));
+        assert_eq!(command(&keymap, "C-x C-s", "edit", "text").as_deref(), Some("buffer.save"));
+        assert_eq!(command(&keymap, "C-x", "edit", "text"), None);
+        assert!(!is_prefix(&keymap, "C-x C-s", "edit"));
+        // prefixes are per mode
+        assert!(!is_prefix(&keymap, "C-x", "prompt"));
+        assert!(matches!(keymap.lookup(&parse_keys("C-x z").unwrap(), "edit", "text"), Lookup::None));
+    }
+
+    #[test]
+    fn unbound_keys() {
+        let mut keymap = Keymap::new();
+        keymap.profile("emacs").unwrap();
+        assert_eq!(command(&keymap, "C-t", "edit", "text"), None);
+
+        // a prefix whose only binding is unbound is no prefix
+        keymap.load_str("\"C-q a\" = \"view.center\"\n\"C-q a\" = \"none\"\n").unwrap();
+        assert!(!is_prefix(&keymap, "C-q", "edit"));
+    }
+
+    #[test]
+    fn keys_of_a_command() {
+        let mut keymap = Keymap::new();
+        keymap.load_str("[mode.insert]\nC-s = \"view.center\"\n").unwrap();
+        assert_eq!(keymap.keys("buffer.save", &["edit"], "text"), vec!["C-s"]);
+        // insert takes C-s before edit does
+        assert!(keymap.keys("buffer.save", &["insert", "edit"], "text").is_empty());
+        assert_eq!(keymap.keys("view.center", &["insert", "edit"], "text"), vec!["C-s"]);
+    }
+
+    #[test]
+    fn scrolling_has_keys_jwin_sends() {
+        let mut keymap = Keymap::new();
+        for mode in ["edit", "listing"] {
+            assert_eq!(command(&keymap, "C-u", mode, "text").as_deref(), Some("view.scroll-up"));
+            assert_eq!(command(&keymap, "C-d", mode, "text").as_deref(), Some("view.scroll-down"));
+        }
+        assert_eq!(command(&keymap, "C-y", "normal", "text").as_deref(), Some("view.scroll-up"));
+        assert_eq!(command(&keymap, "C-e", "normal", "text").as_deref(), Some("view.scroll-down"));
+
+        keymap.profile("emacs").unwrap();
+        assert_eq!(command(&keymap, "Esc v", "edit", "text").as_deref(), Some("view.scroll-up"));
+        assert_eq!(command(&keymap, "C-v", "edit", "text").as_deref(), Some("view.scroll-down"));
+    }
+
+    #[test]
+    fn load_errors() {
+        let mut keymap = Keymap::new();
+        assert_eq!(keymap.load_str("\n[mode]\nC-s = \"x\"\n").unwrap_err(), "line 3: mode.C-s: sections are mode.<name> or filetype.<name>");
+        assert_eq!(keymap.load_str("C-s = \"no.such\"\n").unwrap_err(), "line 1: C-s: unknown command no.such");
+        assert_eq!(keymap.load_str("Nope = \"view.center\"\n").unwrap_err(), "line 1: Nope: invalid keys");
+    }
+}
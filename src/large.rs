@@ -0,0 +1,371 @@
+use std::borrow::Cow;
+use std::cmp;
+use std::fs::File;
+use std::io;
+use std::ops::Range;
+use std::path::Path;
+
+use crate::buffer;
+use crate::format::Encoding;
+
+// read-only access to utf-8 files too big to load
+// lines are found as they are needed and only
+// the ones on screen get decoded
+// invalid bytes past the start show as U+FFFD
+
+// bytes indexed by one call of index_more
+const INDEX_STEP: usize = 16 << 20;
+// bytes the encoding is guessed from
+const SNIFF: usize = 64 << 10;
+// bytes read at once looking for the end of a line
+const WINDOW: usize = 64 << 10;
+
+#[cfg(unix)]
+mod map {
+    use std::borrow::Cow;
+    use std::fs::File;
+    use std::io;
+    use std::ops::Range;
+    use std::os::raw::{c_int, c_void};
+    use std::os::unix::io::AsRawFd;
+    use std::ptr;
+    use std::slice;
+
+    // off_t of the mmap symbol, glibc and bionic
+    // keep a 32 bit one on 32 bit targets
+    #[cfg(any(all(target_os = "linux", not(target_env = "musl")), target_os = "android"))]
+    type Off = std::os::raw::c_long;
+    #[cfg(not(any(all(target_os = "linux", not(target_env = "musl")), target_os = "android")))]
+    type Off = i64;
+
+    extern "C" {
+        fn mmap(addr: *mut c_void, len: usize, prot: c_int, flags: c_int, fd: c_int, offset: Off) -> *mut c_void;
+        fn munmap(addr: *mut c_void, len: usize) -> c_int;
+    }
+
+    // the same on linux and the bsds
+    const PROT_READ: c_int = 1;
+    const MAP_PRIVATE: c_int = 2;
+
+    // a file truncated by another program while it is
+    // mapped kills the editor with SIGBUS like it does less
+    pub struct Map {
+        ptr: *mut c_void,
+        len: usize
+    }
+
+    impl Map {
+        // len must not be 0
+        pub fn new(file: File, len: usize) -> io::Result<Map> {
+            let ptr = unsafe { mmap(ptr::null_mut(), len, PROT_READ, MAP_PRIVATE, file.as_raw_fd(), 0) };
+            if ptr as isize == -1 {
+                return Err(io::Error::last_os_error());
+            }
+
+            Ok(Map {
+                ptr,
+                len
+            })
+        }
+
+        pub fn size(&self) -> usize {
+            self.len
+        }
+
+        pub fn bytes(&self, range: Range<usize>) -> Cow<'_, [u8]> {
+            let bytes = unsafe { slice::from_raw_parts(self.ptr as *const u8, self.len) };
+            Cow::Borrowed(&bytes[range])
+        }
+    }
+
+    impl Drop for Map {
+        fn drop(&mut self) {
+            unsafe {
+                munmap(self.ptr, self.len);
+            }
+        }
+    }
+}
+
+// no mmap, the bytes are read a window
+// at a time as they are needed
+#[cfg(not(unix))]
+mod map {
+    use std::borrow::Cow;
+    use std::fs::File;
+    use std::io::{self, Read, Seek, SeekFrom};
+    use std::ops::Range;
+
+    pub struct Map {
+        file: File,
+        len: usize
+    }
+
+    impl Map {
+        pub fn new(file: File, len: usize) -> io::Result<Map> {
+            Ok(Map {
+                file,
+                len
+            })
+        }
+
+        pub fn size(&self) -> usize {
+            self.len
+        }
+
+        // fewer bytes if the file shrank since it was opened
+        pub fn bytes(&self, range: Range<usize>) -> Cow<'_, [u8]> {
+            let mut bytes = Vec::with_capacity(range.len());
+            let mut file = &self.file;
+            if file.seek(SeekFrom::Start(range.start as u64)).is_ok() {
+                let _ = file.take(range.len() as u64).read_to_end(&mut bytes);
+            }
+            Cow::Owned(bytes)
+        }
+    }
+}
+
+pub struct LargeFile {
+    map: map::Map,
+
+    // byte offset of each line found so far
+    starts: Vec<usize>,
+    // bytes before this are indexed
+    indexed: usize
+}
+
+impl LargeFile {
+    // InvalidData if the file doesn't start as utf-8
+    pub fn open(path: &Path) -> io::Result<LargeFile> {
+        let file = File::open(path)?;
+        let len = file.metadata()?.len() as usize;
+        if len == 0 {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "empty file"));
+        }
+
+        let map = map::Map::new(file, len)?;
+        let start = map.bytes(0..cmp::min(len, SNIFF));
+        if Encoding::detect_start(&start) != Encoding::Utf8 {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "not utf-8"));
+        }
+        // the byte order mark isn't part of the first line
+        let bom = if start.starts_with(&[0xef, 0xbb, 0xbf]) {
+            3
+        } else {
+            0
+        };
+
+        Ok(LargeFile {
+            map,
+
+            starts: vec![bom],
+            indexed: bom
+        })
+    }
+
+    fn bytes(&self, range: Range<usize>) -> Cow<'_, [u8]> {
+        self.map.bytes(range)
+    }
+
+    fn len(&self) -> usize {
+        self.map.size()
+    }
+
+    pub fn is_indexed(&self) -> bool {
+        self.indexed == self.len()
+    }
+
+    // lines found so far, all of them once indexed
+    pub fn lines(&self) -> usize {
+        self.starts.len()
+    }
+
+    // indexes the next step, false when there was nothing left
+    pub fn index_more(&mut self) -> bool {
+        if self.is_indexed() {
+            return false;
+        }
+
+        let len = self.len();
+        let end = cmp::min(self.indexed + INDEX_STEP, len);
+        let from = self.indexed;
+        // a final newline doesn't start another line
+        let found: Vec<usize> = self.map.bytes(from..end).iter()
+            .enumerate()
+            .filter(|(_, x)| **x == b'\n')
+            .map(|(i, _)| from + i + 1)
+            .filter(|x| *x < len)
+            .collect();
+        self.starts.extend(found);
+        self.indexed = end;
+
+        true
+    }
+
+    pub fn index_all(&mut self) {
+        while self.index_more() {}
+    }
+
+    // indexes until line y is known or the file ends
+    pub fn index_to(&mut self, y: usize) {
+        while self.starts.len() <= y && self.index_more() {}
+    }
+
+    // without the line ending
+    pub fn line(&self, y: usize) -> Option<Cow<'_, [u8]>> {
+        let start = *self.starts.get(y)?;
+        let mut end = match self.starts.get(y + 1) {
+            Some(next) => next - 1,
+            None => self.line_end(start)
+        };
+        if end > start && self.bytes(end - 1..end)[0] == b'\r' {
+            end -= 1;
+        }
+
+        Some(self.bytes(start..end))
+    }
+
+    // offset of the newline after start or the end of the file
+    fn line_end(&self, start: usize) -> usize {
+        let len = self.len();
+        let mut from = start;
+        while from < len {
+            let end = cmp::min(from + WINDOW, len);
+            if let Some(i) = self.bytes(from..end).iter().position(|x| *x == b'\n') {
+                return from + i;
+            }
+            from = end;
+        }
+
+        len
+    }
+
+    // chars of line y like a Buffer stores them
+    // with leading tabs tab_width chars wide
+    pub fn chars(&self, y: usize, tab_width: usize) -> Option<Vec<char>> {
+        Some(buffer::stored_chars(&String::from_utf8_lossy(&self.line(y)?), tab_width))
+    }
+
+    // byte offset of stored char x on line y
+    fn offset(&self, x: usize, y: usize, tab_width: usize) -> usize {
+        let line = match self.line(y) {
+            Some(line) => line,
+            None => return self.len()
+        };
+        let tabs = line.iter().take_while(|x| **x == b'\t').count();
+        let col = if x < tabs * tab_width {
+            x / tab_width
+        } else {
+            x - tabs * tab_width + tabs
+        };
+
+        let text = String::from_utf8_lossy(&line);
+        let len = text.char_indices().nth(col).map_or(text.len(), |(i, _)| i);
+        // lossy decoding can only make text longer
+        self.starts[y] + cmp::min(len, line.len())
+    }
+
+    // stored char x and line y of a byte offset
+    fn position(&mut self, offset: usize, tab_width: usize) -> (usize, usize) {
+        while self.indexed <= offset && self.index_more() {}
+        let y = self.starts.partition_point(|x| *x <= offset) - 1;
+        let before = self.bytes(self.starts[y]..offset);
+        let tabs = before.iter().take_while(|x| **x == b'\t').count();
+        let x = String::from_utf8_lossy(&before).chars().count() - tabs + tabs * tab_width;
+
+        (x, y)
+    }
+
+    // first match at or after (x, y), wrapping around
+    // the end like Buffer::find, matches stay in a line
+    pub fn find(&mut self, pattern: &str, x: usize, y: usize, tab_width: usize) -> Option<(usize, usize)> {
+        let pattern = pattern.as_bytes();
+        if pattern.is_empty() {
+            return None;
+        }
+
+        self.index_to(y);
+        let (mut line_y, mut skip) = if y < self.lines() {
+            (y, self.offset(x, y, tab_width) - self.starts[y])
+        } else {
+            (0, 0)
+        };
+        let first = line_y;
+        let mut wrapped = false;
+        loop {
+            let found = self.line(line_y).and_then(|line| {
+                line[skip..].windows(pattern.len()).position(|x| x == pattern)
+            });
+            if let Some(i) = found {
+                let offset = self.starts[line_y] + skip + i;
+                return Some(self.position(offset, tab_width));
+            }
+            // the start of the first line is searched last
+            if wrapped && line_y == first {
+                return None;
+            }
+
+            line_y += 1;
+            skip = 0;
+            self.index_to(line_y);
+            if line_y >= self.lines() {
+                line_y = 0;
+                wrapped = true;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    // a LargeFile of bytes in a temp file
+    fn open(name: &str, bytes: &[u8]) -> io::Result<LargeFile> {
+        let path = std::env::temp_dir().join(format!("jedit-large-{}-{}", std::process::id(), name));
+        fs::write(&path, bytes).unwrap();
+        let large = LargeFile::open(&path);
+        let _ = fs::remove_file(&path);
+        large
+    }
+
+    #[test]
+    fn lines() {
+        let mut large = open("lines", b"a\r\n\tb\nc\n").unwrap();
+        large.index_all();
+        assert_eq!(large.lines(), 3);
+        assert_eq!(large.line(0).as_deref(), Some(&b"a"[..]));
+        assert_eq!(large.chars(1, 4), Some(vec!['\t', '\t', '\t', '\t', 'b']));
+        assert_eq!(large.line(3), None);
+        assert_eq!(large.find("c", 0, 0, 4), Some((0, 2)));
+        assert_eq!(large.find("b", 0, 2, 4), Some((4, 1)));
+    }
+
+    #[test]
+    fn matches_stay_in_a_line() {
+        let mut large = open("find", b"ab\ncd\nab").unwrap();
+        assert_eq!(large.find("b\nc", 0, 0, 4), None);
+        assert_eq!(large.find("bc", 0, 0, 4), None);
+        // wraps around to before the start
+        assert_eq!(large.find("ab", 1, 2, 4), Some((0, 0)));
+        assert_eq!(large.find("ab", 1, 0, 4), Some((0, 2)));
+        assert_eq!(large.find("a", 0, 9, 4), Some((0, 0)));
+    }
+
+    #[test]
+    fn skips_the_byte_order_mark() {
+        let mut large = open("bom", b"\xef\xbb\xbfab\n").unwrap();
+        large.index_all();
+        assert_eq!(large.line(0).as_deref(), Some(&b"ab"[..]));
+        assert_eq!(large.find("a", 1, 0, 4), Some((0, 0)));
+    }
+
+    #[test]
+    fn refuses_other_encodings() {
+        let kind = |x: io::Result<LargeFile>| x.err().map(|x| x.kind());
+        assert_eq!(kind(open("latin1", b"caf\xe9\n")), Some(io::ErrorKind::InvalidData));
+        assert_eq!(kind(open("utf16", b"\xff\xfea\0b\0")), Some(io::ErrorKind::InvalidData));
+        assert_eq!(kind(open("empty", b"")), Some(io::ErrorKind::InvalidInput));
+    }
+}
@@ -1,241 +1,1520 @@
 use std::cmp;
+use std::collections::{HashMap, HashSet};
 use std::env;
-use std::fs::File;
-use std::io::prelude::*;
+use std::fs;
+use std::io::{self, Read, Write};
+use std::panic::{self, AssertUnwindSafe};
+use std::process;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Duration, Instant, SystemTime};
 
 extern crate jwin;
 extern crate jvec;
 
-use jwin::{Code, Event, Win};
-use jvec::JVec;
+use jwin::Win;
 
+mod args;
 mod buffer;
+mod command;
+mod conf;
+mod display;
+mod emacs;
+mod format;
+mod grep;
+mod key;
+mod keymap;
+mod large;
+mod merge;
+mod prompt;
+mod settings;
+mod status;
+mod swap;
 mod syntax;
-
+mod theme;
+#[cfg(unix)]
+mod tty;
+mod undo;
+mod view;
+mod vim;
+mod watch;
+
+use crate::args::Args;
 use crate::buffer::{Buffer, Char};
-
-// configs
+use crate::display::{Display, Event};
+use crate::emacs::Emacs;
+use crate::format::{Encoding, Format};
+use crate::key::Key;
+use crate::keymap::{Keymap, Lookup};
+use crate::large::LargeFile;
+use crate::prompt::{Ask, Prompt};
+use crate::settings::Settings;
+use crate::theme::{Style, Styles, Theme, Themed};
+use crate::undo::History;
+use crate::view::{Align, Cursor, Dir, Layout, Pos, View, Wrap};
+use crate::vim::{Vim, VimMode};
+
+// default tab width, config.toml can change it
 pub const TAB_SIZE: usize = 4;
 
-fn pad(mut string: String, n: usize) -> String {
-    if string.len() < n {
-        for _ in string.len()..n {
-            string.insert(0, ' ');
-        }
-    }
+// commands shown above the palette prompt at once
+const PALETTE_ROWS: usize = 10;
 
-    string
+// what a listing buffer lists
+enum Listing {
+    // one match per line
+    Grep(Vec<grep::Match>),
+    // line i switches to buffer i
+    Buffers
 }
 
-// cursor struct to handle
-// cursor specific things
-#[derive(Copy, Clone, Debug)]
-struct Cursor {
-    x: usize, y: usize,
-    fake_x: usize
+// editor state kept for each buffer
+// cursor and view are where the buffer
+// was left when a view switched away from it
+struct Place {
+    cursor: Cursor,
+    view_x: usize, view_y: usize,
+
+    // Some if the buffer is a listing
+    listing: Option<Listing>,
+
+    history: History
 }
 
-impl Cursor {
-    pub fn new() -> Cursor {
-        Cursor {
-            x: 0, y: 0,
-            fake_x: 0
+impl Place {
+    fn new() -> Place {
+        Place {
+            cursor: Cursor::new(),
+            view_x: 0, view_y: 0,
+
+            listing: None,
+
+            history: History::new()
         }
     }
+
+    fn save(&mut self, view: &View) {
+        self.cursor = view.cursor;
+        self.view_x = view.view_x;
+        self.view_y = view.view_y;
+    }
+
+    fn restore(&self, view: &mut View) {
+        view.cursor = self.cursor;
+        view.view_x = self.view_x;
+        view.view_y = self.view_y;
+    }
 }
 
 struct JEdit {
-    win: Win,
+    win: Themed,
+
+    buffers: Vec<Buffer>,
+    places: Vec<Place>,
 
-    buffer: Buffer,
+    views: Vec<View>,
+    layout: Layout,
+    // index of the view with the keyboard
+    focus: usize,
 
     width: usize, height: usize,
-    buffer_width: usize, buffer_height: usize,
 
-    cursor: Cursor,
+    // shown in the status bar until the next key
+    message: Option<String>,
+
+    // Some while keys go to the prompt
+    prompt: Option<Prompt>,
+    histories: HashMap<Ask, Vec<String>>,
 
-    view_x: usize,
-    view_y: usize,
+    keymap: Keymap,
+    // keys of an unfinished sequence
+    pending: Vec<Key>,
+    // None after typed text
+    last_command: Option<String>,
 
-    // line number offset
-    offset_x: usize
+    settings: Settings,
+    // token styles of the theme
+    styles: Styles,
+
+    emacs: Emacs,
+
+    // Some while modal editing is on
+    vim: Option<Vim>,
+    // ends the main loop
+    quit: bool,
+
+    // swap files found when opening, oldest first
+    recoveries: Vec<(PathBuf, String)>,
+    last_swap: Instant,
+    // files this session wrote swap files for
+    // only those are cleaned up on quit
+    swapped: HashSet<PathBuf>,
+
+    // buffer, time and text of a file that changed
+    // on disk while it had unsaved changes
+    changed: Option<(usize, Option<SystemTime>, String, Format)>,
+    last_check: Instant,
+
+    // bytes for stdout once the stdin buffer is confirmed
+    output: Option<Vec<u8>>
 }
 
 impl JEdit {
-    pub fn set_str(&mut self, string: &str) {
-        self.buffer = Buffer::new(Some(Box::new(syntax::Rust::new())));
-        self.handle_str(string);
+    fn new(win: Box<dyn Display>) -> JEdit {
+        JEdit {
+            win: Themed::new(win),
+
+            buffers: Vec::new(),
+            places: Vec::new(),
+
+            views: vec![View::new(0)],
+            layout: Layout::View(0),
+            focus: 0,
+
+            width: 0, height: 0,
+
+            message: None,
+
+            prompt: None,
+            histories: HashMap::new(),
+
+            keymap: Keymap::new(),
+            pending: Vec::new(),
+            last_command: None,
+
+            settings: Settings::new(),
+            styles: [Style::default(); 8],
+
+            emacs: Emacs::new(),
+
+            vim: None,
+            quit: false,
+
+            recoveries: Vec::new(),
+            last_swap: Instant::now(),
+            swapped: HashSet::new(),
+
+            changed: None,
+            last_check: Instant::now(),
+
+            output: None
+        }
+    }
+
+    fn view(&self) -> &View {
+        &self.views[self.focus]
+    }
+
+    fn view_mut(&mut self) -> &mut View {
+        &mut self.views[self.focus]
+    }
+
+    // index of the focused buffer
+    fn current(&self) -> usize {
+        self.view().buffer
+    }
+
+    fn buffer(&self) -> &Buffer {
+        &self.buffers[self.current()]
+    }
+
+    fn listing(&self) -> Option<&Listing> {
+        self.places[self.current()].listing.as_ref()
+    }
+
+    // adds a buffer filled with string and shows it
+    fn add_buffer(&mut self, buffer: Buffer, string: &str, listing: Option<Listing>) {
+        self.buffers.push(buffer);
+        self.places.push(Place::new());
+        self.switch(self.buffers.len() - 1);
+
+        let current = self.current();
+        self.buffers[current].set_text(string);
         self.move_cursor(0, 0);
+        self.buffers[current].mark_saved();
+        self.places[current].listing = listing;
+
+        // loading is no step to undo
+        self.buffers[current].take_edits();
     }
 
-    fn move_cursor(&mut self, x: usize, y: usize) {
-        self.cursor.x = x;
-        self.cursor.y = y;
+    // shows buffer i in the focused view
+    fn switch(&mut self, i: usize) {
+        if i >= self.buffers.len() {
+            return;
+        }
 
-        // cursor.x fix non paging
-        if self.cursor.x < (self.buffer_width / 4) + self.view_x {
-            if self.cursor.x >= self.buffer_width / 4 {
-                self.view_x = self.cursor.x - (self.buffer_width / 4);
-            } else {
-                self.view_x = 0;
+        let view = &mut self.views[self.focus];
+        // there is nothing to save before the first buffer
+        if view.buffer < self.places.len() {
+            self.places[view.buffer].save(view);
+        }
+
+        view.buffer = i;
+        self.places[i].restore(view);
+    }
+
+    fn cycle(&mut self, forward: bool) {
+        let len = self.buffers.len();
+        let current = self.current();
+        if forward {
+            self.switch((current + 1) % len);
+        } else {
+            self.switch((current + len - 1) % len);
+        }
+    }
+
+    // empty rust buffer without a file
+    fn scratch(&self) -> Buffer {
+        let mut buffer = Buffer::new(Some(Box::new(syntax::Rust::new())));
+        self.settings.apply(&mut buffer);
+        buffer
+    }
+
+    // asks before throwing away changes
+    fn request_close(&mut self) {
+        if self.buffer().modified() {
+            let label = format!("{} has unsaved changes, save, discard or cancel? [s/d/c]", self.buffer().name());
+            self.ask(Ask::Close, label.as_str(), "");
+        } else {
+            self.close();
+        }
+    }
+
+    fn request_quit(&mut self) {
+        match self.buffers.iter().find(|x| x.is_pipe()) {
+            Some(buffer) => {
+                let label = format!("write {} to stdout? [y/n/c]", buffer.name());
+                self.ask(Ask::Pipe, label.as_str(), "");
+            },
+            None => self.request_quit_files()
+        }
+    }
+
+    // the stdin buffer goes to stdout instead of a file
+    fn request_quit_files(&mut self) {
+        let unsaved = self.buffers.iter().filter(|x| x.modified() && !x.is_pipe()).count();
+        if unsaved == 0 {
+            self.quit = true;
+        } else {
+            let label = format!("{} buffers have unsaved changes, save all, discard or cancel? [s/d/c]", unsaved);
+            self.ask(Ask::Quit, label.as_str(), "");
+        }
+    }
+
+    // false if a buffer couldn't be saved
+    fn save_all(&mut self) -> bool {
+        for buffer in self.buffers.iter_mut().filter(|x| x.modified() && !x.is_pipe()) {
+            if let Err(err) = buffer.save() {
+                self.message = Some(format!("couldn't save {}: {}", buffer.name(), err));
+                return false;
+            }
+            if let Some(path) = buffer.path() {
+                swap::remove(path);
+            }
+        }
+
+        true
+    }
+
+    // there is always at least one buffer
+    fn close(&mut self) {
+        let closed = self.current();
+        if let Some(path) = self.buffers[closed].path() {
+            if self.swapped.remove(path) {
+                swap::remove(path);
             }
         }
+        self.buffers.remove(closed);
+        self.places.remove(closed);
+        // buffer indices shift so a pending question is dropped
+        self.changed = None;
+
+        if self.buffers.is_empty() {
+            let scratch = self.scratch();
+            self.buffers.push(scratch);
+            self.places.push(Place::new());
+        }
+
+        // views of the closed buffer fall back to a neighbour
+        let fallback = cmp::min(closed, self.buffers.len() - 1);
+        for view in self.views.iter_mut() {
+            if view.buffer == closed {
+                view.buffer = fallback;
+                self.places[fallback].restore(view);
+            } else if view.buffer > closed {
+                view.buffer -= 1;
+            }
+        }
+    }
+
+    // keeps other views of buffer on the same text
+    // when a line is inserted or removed at y
+    fn shift_views(&mut self, buffer: usize, y: usize, inserted: bool) {
+        let focus = self.focus;
+        for (i, view) in self.views.iter_mut().enumerate() {
+            if i == focus || view.buffer != buffer {
+                continue;
+            }
 
-        if self.cursor.x >= (3 * self.buffer_width / 4) + self.view_x {
-            if self.cursor.x >= (3 * self.buffer_width / 4) {
-                self.view_x = self.cursor.x - (3 * self.buffer_width / 4);
+            if inserted {
+                if view.cursor.y >= y {
+                    view.cursor.y += 1;
+                }
+                if view.view_y >= y {
+                    view.view_y += 1;
+                }
             } else {
-                if self.buffer_width >= 1 {
-                    self.view_x = self.buffer_width - 1;
+                if view.cursor.y >= y && view.cursor.y != 0 {
+                    view.cursor.y -= 1;
+                }
+                if view.view_y >= y && view.view_y != 0 {
+                    view.view_y -= 1;
                 }
             }
         }
+    }
+
+    // keeps every view of buffer inside its text
+    // after the whole text was replaced
+    fn clamp_views(&mut self, buffer: usize) {
+        let height = self.buffers[buffer].height();
+        let last = height.saturating_sub(1);
+        for view in self.views.iter_mut().filter(|x| x.buffer == buffer) {
+            view.cursor.y = cmp::min(view.cursor.y, last);
+            view.cursor.x = cmp::min(view.cursor.x, self.buffers[buffer].line_len(view.cursor.y));
+            view.view_y = cmp::min(view.view_y, last);
+        }
 
-        // cursor.y fix non paging
-        if self.cursor.y < (self.buffer_height / 4) + self.view_y {
-            if self.cursor.y >= self.buffer_height / 4 {
-                self.view_y = self.cursor.y - (self.buffer_height / 4);
-            } else {
-                self.view_y = 0;
+        let place = &mut self.places[buffer];
+        place.cursor.y = cmp::min(place.cursor.y, last);
+        place.cursor.x = cmp::min(place.cursor.x, self.buffers[buffer].line_len(place.cursor.y));
+        place.view_y = cmp::min(place.view_y, last);
+    }
+
+    // recolors everything on the next redraw
+    fn set_theme(&mut self, name: &str) {
+        match Theme::load(name) {
+            Ok(theme) => {
+                theme.apply(&mut self.win);
+                self.styles = theme.styles;
+                self.settings.theme = String::from(name);
+            },
+            Err(err) => self.message = Some(err)
+        }
+    }
+
+    // settings that aren't read when needed
+    fn use_settings(&mut self) {
+        let theme = self.settings.theme.clone();
+        self.set_theme(theme.as_str());
+
+        match self.settings.keymap.as_str() {
+            "emacs" => if let Err(err) = self.keymap.profile("emacs") {
+                self.message = Some(err);
+            },
+            "vim" => self.vim = Some(Vim::new()),
+            _ => ()
+        }
+
+        for view in self.views.iter_mut() {
+            view.line_numbers = self.settings.line_numbers;
+            view.wrap = self.settings.wrap;
+            view.margin_x = self.settings.side_scroll_margin;
+            view.margin_y = self.settings.scroll_margin;
+        }
+    }
+
+    fn align(&mut self, align: Align) {
+        self.view_mut().align(align);
+    }
+
+    fn scroll_by(&mut self, lines: isize) {
+        let height = self.buffer().height();
+        self.view_mut().scroll_by(lines, height);
+    }
+
+    // wraps like the wrap setting or at words
+    fn toggle_wrap(&mut self) {
+        let wrap = match (self.view().wrap, self.settings.wrap) {
+            (Wrap::Off, Wrap::Off) => Wrap::Word,
+            (Wrap::Off, wrap) => wrap,
+            _ => Wrap::Off
+        };
+        self.view_mut().wrap = wrap;
+    }
+
+    // changes how the focused buffer is saved
+    fn convert(&mut self, f: fn(&mut Format)) {
+        if self.listing().is_some() {
+            return;
+        }
+
+        let current = self.current();
+        if !self.buffers[current].check_edit() {
+            return;
+        }
+
+        let mut format = self.buffer().format();
+        f(&mut format);
+        self.buffers[current].set_format(format);
+    }
+
+    // navigation and search keep working
+    fn toggle_readonly(&mut self) {
+        if self.listing().is_some() {
+            return;
+        }
+
+        let current = self.current();
+        if self.buffers[current].is_large() {
+            self.message = Some(String::from("large files are always read-only"));
+            return;
+        }
+        let readonly = !self.buffers[current].is_readonly();
+        self.buffers[current].set_readonly(readonly);
+        self.message = Some(String::from(if readonly {
+            "read-only"
+        } else {
+            "writable"
+        }));
+    }
+
+    // the new view shows the same buffer at the same spot
+    fn split(&mut self, dir: Dir) {
+        let view = self.view();
+        let mut new_view = View::new(view.buffer);
+        new_view.line_numbers = view.line_numbers;
+        new_view.wrap = view.wrap;
+        new_view.margin_x = view.margin_x;
+        new_view.margin_y = view.margin_y;
+        new_view.cursor = view.cursor;
+        new_view.view_x = view.view_x;
+        new_view.view_y = view.view_y;
+
+        self.views.push(new_view);
+        let new_focus = self.views.len() - 1;
+        self.layout.split(self.focus, new_focus, dir);
+        self.focus = new_focus;
+    }
+
+    // closes the focused view unless it is the last
+    fn unsplit(&mut self) {
+        if self.views.len() == 1 {
+            return;
+        }
+
+        let current = self.current();
+        self.places[current].save(&self.views[self.focus]);
+
+        self.views.remove(self.focus);
+        self.layout.remove(self.focus);
+        self.focus = cmp::min(self.focus, self.views.len() - 1);
+    }
+
+    // moves focus to the next view in screen order
+    fn cycle_focus(&mut self) {
+        let mut order = Vec::new();
+        self.layout.order(&mut order);
+
+        let i = order.iter().position(|x| *x == self.focus).unwrap_or(0);
+        self.focus = order[(i + 1) % order.len()];
+    }
+
+    // switches to the buffer if it is already open
+    pub fn open(&mut self, path: &Path) -> io::Result<()> {
+        self.open_with(path, None, None)
+    }
+
+    // the syntax and encoding are guessed when None
+    fn open_with(&mut self, path: &Path, syntax_maybe: Option<&str>, encoding_maybe: Option<Encoding>) -> io::Result<()> {
+        let path = match fs::canonicalize(path) {
+            Ok(path) => path,
+            Err(ref err) if err.kind() == io::ErrorKind::NotFound => absolute(path)?,
+            Err(err) => return Err(err)
+        };
+        let open_maybe = self.buffers.iter().position(|x| x.path() == Some(path.as_path()));
+        if let Some(i) = open_maybe {
+            self.switch(i);
+            return Ok(());
+        }
+
+        // large files are read as utf-8 where they are shown
+        // so files in other encodings are read whole
+        let size = fs::metadata(&path).map_or(0, |x| x.len());
+        let limit = self.settings.large_file_size << 20;
+        if limit != 0 && size > limit && encoding_maybe.is_none_or(|x| x == Encoding::Utf8) {
+            match self.open_large(path.clone()) {
+                Err(ref err) if err.kind() == io::ErrorKind::InvalidData => {
+                    self.message = Some(format!("{} isn't utf-8 so it is read whole", path.display()));
+                },
+                res => return res
             }
         }
 
-        if self.cursor.y >= (3 * self.buffer_height / 4) + self.view_y {
-            if self.cursor.y >= (3 * self.buffer_height / 4) {
-                self.view_y = self.cursor.y - (3 * self.buffer_height / 4);
-            } else {
-                if self.buffer_height >= 1 {
-                    self.view_y = self.buffer_height - 1;
+        // a file that doesn't exist yet is created on save
+        let (string, format) = match format::read(&path, encoding_maybe) {
+            Ok(read) => read,
+            Err(ref err) if err.kind() == io::ErrorKind::NotFound => {
+                self.message = Some(format!("new file {}", path.display()));
+                (String::new(), Format::new())
+            },
+            Err(err) => return Err(err)
+        };
+        let syntax = syntax_maybe.and_then(syntax::by_name).unwrap_or_else(|| self.settings.syntax_for(&path));
+        let mut buffer = Buffer::new(Some(syntax));
+        buffer.set_path(Some(path.clone()));
+        buffer.set_format(format);
+        buffer.set_disk(fs::metadata(&path).and_then(|x| x.modified()).ok(), string.clone());
+        self.settings.apply(&mut buffer);
+        self.add_buffer(buffer, string.as_str(), None);
+        if format.mixed {
+            self.message = Some(format!("{} has mixed line endings, saving makes them {}", path.display(), format.eol.name()));
+        }
+
+        // set after loading since loading is an edit
+        if path.exists() && fs::OpenOptions::new().append(true).open(&path).is_err() {
+            let current = self.current();
+            self.buffers[current].set_readonly(true);
+        }
+
+        if let Some(swap) = swap::newer(&path, self.buffer().text().as_str()) {
+            self.recoveries.push((path, swap));
+            if self.prompt.is_none() {
+                self.next_recovery();
+            }
+        }
+
+        Ok(())
+    }
+
+    // only the lines on screen are decoded
+    // fails with InvalidData for files that aren't utf-8
+    fn open_large(&mut self, path: PathBuf) -> io::Result<()> {
+        let mut buffer = Buffer::new(None);
+        buffer.set_path(Some(path.clone()));
+        self.settings.apply(&mut buffer);
+        buffer.set_large(LargeFile::open(&path)?);
+        self.add_buffer(buffer, "", None);
+
+        let current = self.current();
+        self.buffers[current].set_readonly(true);
+        self.message = Some(format!("{} is large, opened read-only without highlighting", path.display()));
+
+        Ok(())
+    }
+
+    fn index_all(&mut self) {
+        let current = self.current();
+        self.buffers[current].index_all();
+    }
+
+    // reads the focused file again in another encoding
+    fn reopen(&mut self, name: &str) {
+        let encoding = match Encoding::by_name(name) {
+            Some(encoding) => encoding,
+            None => {
+                self.message = Some(format!("no encoding {}", name));
+                return;
+            }
+        };
+        let path = match self.buffer().path() {
+            Some(path) => path.to_path_buf(),
+            None => return
+        };
+        // large files are never read whole
+        if self.buffer().is_large() {
+            self.message = Some(String::from("large files can't be reopened"));
+            return;
+        }
+        if self.buffer().modified() {
+            self.message = Some(String::from("save or discard the changes first"));
+            return;
+        }
+
+        match format::read(&path, Some(encoding)) {
+            Ok((text, format)) => {
+                self.replace_text(text.as_str());
+                let current = self.current();
+                self.buffers[current].set_format(format);
+                self.buffers[current].mark_saved();
+                self.buffers[current].set_disk(fs::metadata(&path).and_then(|x| x.modified()).ok(), text);
+            },
+            Err(err) => self.message = Some(format!("couldn't reopen: {}", err))
+        }
+    }
+
+    // the stdin buffer of jedit -
+    fn open_stdin(&mut self, syntax_maybe: Option<&str>, encoding_maybe: Option<Encoding>) -> io::Result<()> {
+        let mut bytes = Vec::new();
+        io::stdin().read_to_end(&mut bytes)?;
+        let (string, format) = Format::decode(bytes.as_slice(), encoding_maybe)?;
+
+        let mut buffer = match syntax_maybe.and_then(syntax::by_name) {
+            Some(syntax) => {
+                let mut buffer = Buffer::new(Some(syntax));
+                self.settings.apply(&mut buffer);
+                buffer
+            },
+            None => self.scratch()
+        };
+        buffer.set_pipe(true);
+        buffer.set_format(format);
+        self.add_buffer(buffer, string.as_str(), None);
+
+        Ok(())
+    }
+
+    // asks about the oldest swap file found
+    fn next_recovery(&mut self) {
+        if let Some((path, _)) = self.recoveries.first() {
+            let label = format!("{} has a newer swap file, recover, delete or ignore? [r/d/i]", path.display());
+            self.ask(Ask::Recover, label.as_str(), "");
+        }
+    }
+
+    fn recover(&mut self, answer: &str) {
+        let (path, swap) = self.recoveries.remove(0);
+        match answer {
+            "r" => if let Some(i) = self.buffers.iter().position(|x| x.path() == Some(path.as_path())) {
+                self.switch(i);
+                self.replace_text(swap.as_str());
+            },
+            "d" => swap::remove(&path),
+            _ => ()
+        }
+    }
+
+    // new contents for the focused buffer
+    // read-only buffers too since the text comes from disk
+    fn replace_text(&mut self, string: &str) {
+        let current = self.current();
+        let readonly = self.buffers[current].is_readonly();
+        self.buffers[current].set_readonly(false);
+
+        self.buffers[current].set_text(string);
+        self.clamp_views(current);
+        self.move_cursor(0, 0);
+        self.view_mut().cursor.fake_x = 0;
+
+        self.buffers[current].set_readonly(readonly);
+    }
+
+    // writes swap files for modified buffers
+    // every swap_interval seconds
+    // returns the swap files written
+    fn autosave(&mut self, force: bool) -> Vec<PathBuf> {
+        let interval = Duration::from_secs(self.settings.swap_interval);
+        if !force && (interval.is_zero() || self.last_swap.elapsed() < interval) {
+            return Vec::new();
+        }
+        self.last_swap = Instant::now();
+
+        let mut written = Vec::new();
+        for buffer in self.buffers.iter().filter(|x| x.modified()) {
+            if let Some(path) = buffer.path() {
+                match swap::write(path, buffer.text().as_str()) {
+                    Ok(swap) => {
+                        self.swapped.insert(path.to_path_buf());
+                        written.push(swap);
+                    },
+                    Err(err) => self.message = Some(format!("couldn't write swap file: {}", err))
                 }
             }
         }
+
+        written
     }
 
-    // must call after you clear the line
-    fn redraw_line(&mut self, y: usize) {
-        let line_maybe = self.buffer.line(y + self.view_y);
-        if line_maybe.is_none() {
-            for _ in 0..self.buffer_width {
-                self.win.put_char(self.offset_x, y, ' ');
+    fn list_buffers(&mut self) {
+        let mut listing = String::new();
+        for (i, buffer) in self.buffers.iter().enumerate() {
+            if i != 0 {
+                listing.push('\n');
             }
+            listing.push_str(format!("{}: {}", i, buffer.name()).as_str());
+        }
+
+        self.add_buffer(Buffer::new(None), listing.as_str(), Some(Listing::Buffers));
+    }
+
+    // alphanumeric run around the cursor
+    fn word_at_cursor(&self) -> String {
+        let cursor = self.view().cursor;
+        let line = match self.buffer().line(cursor.y) {
+            Some(line) => line,
+            None => return String::new()
+        };
+        let is_word = |x: usize| match line[x].as_ref() {
+            Some(chr) => chr.chr.is_alphanumeric() || chr.chr == '_',
+            None => false
+        };
+
+        let mut start = cursor.x;
+        while start > 0 && is_word(start - 1) {
+            start -= 1;
+        }
+        let mut end = cursor.x;
+        while is_word(end) {
+            end += 1;
+        }
+
+        (start..end).map(|x| line[x].as_ref().unwrap().chr).collect()
+    }
+
+    // shows matches in a new listing buffer
+    fn grep(&mut self, pattern: &str) {
+        let root = env::current_dir().unwrap_or_default();
+        let matches = grep::grep(&root, pattern);
+
+        let mut listing = String::new();
+        for (i, m) in matches.iter().enumerate() {
+            if i != 0 {
+                listing.push('\n');
+            }
+            let path = m.path.strip_prefix(&root).unwrap_or(&m.path);
+            listing.push_str(format!("{}:{}:{}: {}", path.display(), m.line + 1, m.col + 1, m.text.replace('\t', " ")).as_str());
+        }
+
+        self.add_buffer(Buffer::new(None), listing.as_str(), Some(Listing::Grep(matches)));
+    }
+
+    // acts on the listing line under the cursor
+    fn pick(&mut self) {
+        let y = self.view().cursor.y;
+        let m = match self.listing() {
+            Some(Listing::Grep(matches)) => match matches.get(y) {
+                Some(m) => m.clone(),
+                None => return
+            },
+            Some(Listing::Buffers) => {
+                let listing = self.current();
+                if y >= self.buffers.len() || y == listing {
+                    return;
+                }
+
+                // the buffer list is single use and closing
+                // it moves the buffers after it down by one
+                self.close();
+                self.switch(if y > listing {
+                    y - 1
+                } else {
+                    y
+                });
+                return;
+            },
+            None => return
+        };
+        if self.open(&m.path).is_err() {
             return;
         }
 
-        let line = line_maybe.as_ref().unwrap();
-        for x in 0..self.buffer_width {
-            let chr_maybe = line[x + self.view_x].as_ref();
-            if chr_maybe.is_none() {
-                self.win.put_char(x + self.offset_x, y, ' ');
-                continue;
+        self.go_to(m.col, m.line);
+    }
+
+    // col counts a tab as one char like files do
+    fn go_to(&mut self, col: usize, line: usize) {
+        self.index_all();
+        let y = cmp::min(line, self.buffer().height().saturating_sub(1));
+        let rows = cmp::max(self.view().buffer_height, 1);
+        let current = self.current();
+        self.buffers[current].load_around(y, rows);
+
+        // leading tabs take tab_width chars in the buffer
+        let mut tabs = 0;
+        while self.buffer().get(tabs, y).map(|x| x.chr) == Some('\t') {
+            tabs += 1;
+        }
+        let x = cmp::min(col + tabs - tabs / self.buffer().tab_width(), self.buffer().line_len(y));
+
+        self.move_cursor(x, y);
+        self.view_mut().cursor.fake_x = x;
+    }
+
+    fn move_cursor(&mut self, x: usize, y: usize) {
+        self.view_mut().move_cursor(x, y);
+    }
+
+    fn ask(&mut self, ask: Ask, label: &str, text: &str) {
+        let history = self.histories.get(&ask).cloned().unwrap_or_default();
+        self.prompt = Some(Prompt::new(ask, label, text, history));
+    }
+
+    fn with_prompt(&mut self, f: fn(&mut Prompt)) {
+        if let Some(prompt) = self.prompt.as_mut() {
+            f(prompt);
+        }
+    }
+
+    fn prompt_accept(&mut self) {
+        if let Some(prompt) = self.prompt.take() {
+            let mut text = prompt.text();
+            // a command picked from the palette
+            if prompt.ask == Ask::Command && prompt.selected() != 0 {
+                if let Some(command) = command::fuzzy(text.as_str()).get(prompt.selected()) {
+                    text = String::from(command.name);
+                }
             }
-                
-            let chr = chr_maybe.clone().unwrap();
-            self.win.set_fg(chr.fg);
-            self.win.set_bg(chr.bg);
-            self.win.set_font(chr.font);
-            self.win.put_char(x + self.offset_x, y, if chr.chr == '\t' {
-                ' '
+            self.answer(prompt.ask, text);
+        }
+        // another question may have taken the place of one
+        if self.prompt.is_none() {
+            self.next_recovery();
+        }
+    }
+
+    // up and down pick from the palette
+    // and go through the history elsewhere
+    fn prompt_move(&mut self, up: bool) {
+        if let Some(prompt) = self.prompt.as_mut() {
+            if prompt.ask == Ask::Command {
+                // the best match is drawn at the bottom
+                let len = command::fuzzy(prompt.text().as_str()).len();
+                prompt.select(up, len);
+            } else if up {
+                prompt.history_prev();
             } else {
-                chr.chr
-            });
+                prompt.history_next();
+            }
         }
     }
 
-    fn redraw(&mut self) {
-        let end = cmp::min(self.buffer_height + self.view_y, self.buffer.height());
-        let line_range = self.view_y..end;
+    fn prompt_cancel(&mut self) {
+        // esc ignores a swap file and asks about the next
+        let ask_maybe = self.prompt.take().map(|x| x.ask);
+        if ask_maybe == Some(Ask::Recover) && !self.recoveries.is_empty() {
+            self.recoveries.remove(0);
+        }
+        self.isearch_end(true);
+        if self.prompt.is_none() {
+            self.next_recovery();
+        }
+    }
 
-        self.offset_x = if end == 0 {
-            0
-        } else {
-            format!("{}", end - 1).len() // good logarithm
+    fn prompt_complete(&mut self) {
+        let (ask, text) = match self.prompt.as_ref() {
+            Some(prompt) => (prompt.ask, prompt.text()),
+            None => return
         };
-        self.offset_x += 1;
-        if self.width >= self.offset_x {
-            self.buffer_width = self.width - self.offset_x;
-        } else {
-            self.buffer_width = 0;
+        let candidates = self.candidates(ask, text.as_str());
+        if let Some(prompt) = self.prompt.as_mut() {
+            prompt.complete(candidates);
+        }
+    }
+
+    // tab completions for the prompt
+    fn candidates(&self, ask: Ask, text: &str) -> Vec<String> {
+        match ask {
+            Ask::Open => prompt::complete_path(text),
+            Ask::Search | Ask::ISearch | Ask::Grep => {
+                if text.is_empty() {
+                    return Vec::new();
+                }
+
+                let mut words: Vec<String> = self.buffer().text()
+                    .split(|x: char| !(x.is_alphanumeric() || x == '_'))
+                    .filter(|x| x.starts_with(text))
+                    .map(String::from)
+                    .collect();
+                words.sort();
+                words.dedup();
+
+                words
+            },
+            Ask::Theme => theme::names().into_iter().filter(|x| x.starts_with(text)).collect(),
+            Ask::Encoding => format::ENCODINGS.iter().map(|x| String::from(x.name())).filter(|x| x.starts_with(text)).collect(),
+            Ask::Line | Ask::Ex | Ask::Close | Ask::Quit | Ask::Pipe | Ask::Recover | Ask::Changed => Vec::new(),
+            Ask::Command => command::fuzzy(text).into_iter().map(|x| String::from(x.name)).collect()
+        }
+    }
+
+    fn answer(&mut self, ask: Ask, text: String) {
+        let history = self.histories.entry(ask).or_default();
+        if !text.is_empty() && history.last() != Some(&text) {
+            history.push(text.clone());
+        }
+
+        match ask {
+            Ask::Open => if let Err(err) = self.open(Path::new(&text)) {
+                self.message = Some(format!("couldn't open {}: {}", text, err));
+            },
+            Ask::Line => self.go_to_line(text.as_str()),
+            Ask::Search => self.search(text.as_str()),
+            Ask::ISearch => self.isearch_end(false),
+            Ask::Grep => self.grep(text.as_str()),
+            Ask::Ex => self.ex(text.as_str()),
+            Ask::Theme => self.set_theme(text.as_str()),
+            Ask::Encoding => self.reopen(text.as_str()),
+            Ask::Close => match text.as_str() {
+                "s" => {
+                    self.save();
+                    if !self.buffer().modified() {
+                        self.close();
+                    }
+                },
+                "d" => self.close(),
+                _ => ()
+            },
+            Ask::Recover => self.recover(text.as_str()),
+            Ask::Changed => self.answer_changed(text.as_str()),
+            Ask::Pipe => match text.as_str() {
+                "y" => {
+                    if let Some(buffer) = self.buffers.iter().find(|x| x.is_pipe()) {
+                        let text = buffer.text();
+                        self.output = Some(match buffer.format().encode(text.as_str()) {
+                            Ok(bytes) => bytes,
+                            Err(_) => buffer.format().apply(text.as_str()).into_bytes()
+                        });
+                    }
+                    self.request_quit_files();
+                },
+                "n" => {
+                    self.output = None;
+                    self.request_quit_files();
+                },
+                _ => ()
+            },
+            Ask::Quit => match text.as_str() {
+                "s" => self.quit = self.save_all(),
+                "d" => self.quit = true,
+                _ => ()
+            },
+            // the best match unless the name is exact
+            Ask::Command => {
+                let command_maybe = command::find(text.as_str()).or_else(|| command::fuzzy(text.as_str()).first().copied());
+                match command_maybe {
+                    Some(command) => (command.run)(self),
+                    None => self.message = Some(format!("no command: {}", text))
+                }
+            }
+        }
+    }
+
+    // 1-based like the gutter
+    fn go_to_line(&mut self, text: &str) {
+        let line = match text.trim().parse::<usize>() {
+            Ok(line) => line,
+            Err(_) => {
+                self.message = Some(format!("not a line number: {}", text));
+                return;
+            }
+        };
+
+        self.index_all();
+        let y = cmp::min(line.saturating_sub(1), self.buffer().height().saturating_sub(1));
+        self.move_cursor(0, y);
+        self.view_mut().cursor.fake_x = 0;
+    }
+
+    // next match after the cursor
+    fn search(&mut self, pattern: &str) {
+        let cursor = self.view().cursor;
+        let current = self.current();
+        match self.buffers[current].find(pattern, cursor.x + 1, cursor.y) {
+            Some((x, y)) => {
+                self.move_cursor(x, y);
+                self.view_mut().cursor.fake_x = x;
+            },
+            None => self.message = Some(format!("not found: {}", pattern))
         }
+    }
 
-        //self.win.clear();
-        for y in 0..self.buffer_height {
-            if line_range.contains(&(y + self.view_y)) {
-                self.win.set_bg(0);
-                self.win.set_fg(1);
-                self.win.set_font(1);
-                self.win.put_str(0, y, pad(format!("{} ", y + self.view_y), self.offset_x).as_str());
+    fn save(&mut self) {
+        let current = self.current();
+        let buffer = &mut self.buffers[current];
+        self.message = Some(match buffer.save() {
+            Ok(()) => {
+                if let Some(path) = buffer.path() {
+                    swap::remove(path);
+                }
+                format!("saved {}", buffer.name())
+            },
+            Err(err) => format!("couldn't save: {}", err)
+        });
+    }
+
+    // matching commands above the prompt
+    // best match at the bottom and the list
+    // scrolls to keep the selection in sight
+    fn draw_palette(&mut self, bottom: usize) {
+        let (text, selected) = match self.prompt.as_ref() {
+            Some(prompt) => (prompt.text(), prompt.selected()),
+            None => return
+        };
+        let commands = command::fuzzy(text.as_str());
+
+        let rows = cmp::min(cmp::min(commands.len(), PALETTE_ROWS), bottom);
+        if rows == 0 {
+            return;
+        }
+        let first = selected.saturating_sub(rows - 1);
+        // keys for the mode the command will run in
+        let modes = JEdit::modes(self.buffer_mode());
+        for (i, command) in commands.iter().enumerate().skip(first).take(rows) {
+            let keys = self.keymap.keys(command.name, modes, self.buffers[self.current()].syntax_name()).join(", ");
+            let mut line = format!(" {}", command.name);
+            let len = line.chars().count() + keys.chars().count() + 1;
+            if len < self.width {
+                line.push_str(" ".repeat(self.width - len).as_str());
+            }
+            line.push_str(keys.as_str());
+            line.push(' ');
+            let line: String = line.chars().take(self.width).collect();
 
+            if i == selected {
+                self.win.set_bg(theme::SELECTION);
             } else {
-                self.win.put_str(0, y, " ".repeat(self.offset_x).as_str());
+                self.win.set_bg(theme::BACKGROUND);
             }
+            self.win.set_fg(theme::FOREGROUND);
+            self.win.set_font(theme::FONT_REGULAR);
+            self.win.put_str(0, bottom - 1 - (i - first), line.as_str());
+        }
+    }
 
-            self.win.set_font(0);
-            self.redraw_line(y);
+    fn redraw(&mut self) {
+        // the last line is the status bar
+        let height = self.height.saturating_sub(1);
+        self.layout.place(&mut self.views, &mut self.win, 0, 0, self.width, height);
+        for (i, view) in self.views.iter_mut().enumerate() {
+            self.buffers[view.buffer].load_around(view.view_y, view.buffer_height);
+            view.draw(&mut self.win, &self.buffers[view.buffer], i == self.focus, &self.styles);
         }
-        
-        if self.cursor.y >= self.view_y {
-            self.win.set_bg(0);
-            self.win.set_fg(1);
-            self.win.set_font(1);
-            self.win.put_str(self.cursor.x + self.offset_x - self.view_x, self.cursor.y - self.view_y, "|");
+
+        if let Some(prompt) = self.prompt.as_ref() {
+            if prompt.ask == Ask::Command {
+                self.draw_palette(height);
+            }
+        }
+
+        // the prompt takes the place of the status bar
+        if self.height != 0 {
+            match self.prompt.as_ref() {
+                Some(prompt) => prompt.draw(&mut self.win, height, self.width),
+                None => {
+                    let view = &self.views[self.focus];
+                    let message = self.message.as_deref().or(self.vim_label());
+                    status::draw(&mut self.win, height, self.width, &self.buffers[view.buffer], view, message);
+                }
+            }
         }
 
         self.win.flush();
     }
 
+    // text between two positions, end exclusive
+    // tabs come out expanded like they are stored
+    pub fn text_range(&self, start: Pos, end: Pos) -> String {
+        let buffer = self.buffer();
+        let mut string = String::new();
+        for y in start.1..cmp::min(end.1 + 1, buffer.height()) {
+            if y != start.1 {
+                string.push('\n');
+            }
+
+            let from = if y == start.1 {
+                start.0
+            } else {
+                0
+            };
+            let to = if y == end.1 {
+                cmp::min(end.0, buffer.line_len(y))
+            } else {
+                buffer.line_len(y)
+            };
+            string.extend((from..to).filter_map(|x| buffer.get(x, y)).map(|x| x.chr));
+        }
+
+        string
+    }
+
+    // leaves the cursor at start
+    pub fn delete_range(&mut self, start: Pos, end: Pos) -> String {
+        let b = self.current();
+        let text = self.text_range(start, end);
+        // the text can still be yanked
+        if !self.buffers[b].check_edit() {
+            return text;
+        }
+
+        let height = self.buffers[b].height();
+        let end = if end.1 >= height && height != 0 {
+            (self.buffers[b].line_len(height - 1), height - 1)
+        } else {
+            end
+        };
+
+        if start.1 == end.1 {
+            let to = cmp::min(end.0, self.buffers[b].line_len(start.1));
+            for _ in start.0..to {
+                self.buffers[b].remove(start.0, start.1);
+            }
+        } else if start.1 < end.1 {
+            for _ in start.0..self.buffers[b].line_len(start.1) {
+                self.buffers[b].remove(start.0, start.1);
+            }
+            for _ in (start.1 + 1)..end.1 {
+                self.buffers[b].remove_line(start.1 + 1);
+                self.shift_views(b, start.1 + 1, false);
+            }
+
+            // the rest of the last line joins the first
+            let tail_maybe = self.buffers[b].remove_line(start.1 + 1);
+            self.shift_views(b, start.1 + 1, false);
+            if let Some(tail) = tail_maybe {
+                for (x, chr) in (start.0..).zip(tail.into_iter().skip(end.0).flatten()) {
+                    self.buffers[b].insert(x, start.1, chr);
+                }
+            }
+        }
+
+        self.move_cursor(start.0, start.1);
+        self.view_mut().cursor.fake_x = start.0;
+
+        text
+    }
+
+    // inserts at the cursor without auto indent
+    // or tab expansion
+    pub fn insert_raw(&mut self, string: &str) {
+        let b = self.current();
+        if !self.buffers[b].check_edit() {
+            return;
+        }
+        for chr in string.chars() {
+            let cursor = self.view().cursor;
+            if chr == '\n' {
+                self.buffers[b].split_line(cursor.x, cursor.y);
+                self.shift_views(b, cursor.y + 1, true);
+                self.move_cursor(0, cursor.y + 1);
+            } else {
+                self.buffers[b].insert(cursor.x, cursor.y, Char::new(chr));
+                self.move_cursor(cursor.x + 1, cursor.y);
+            }
+        }
+
+        let x = self.view().cursor.x;
+        self.view_mut().cursor.fake_x = x;
+    }
+
     fn handle_str(&mut self, string: &str) {
+        let b = self.current();
+        if !self.buffers[b].check_edit() {
+            return;
+        }
         for chr in string.chars() {
+            let cursor = self.view().cursor;
             match chr {
                 '\n' => {
-                    let mut new_line = JVec::new();
-                    let line_maybe = self.buffer.line_mut(self.cursor.y);
-                    let mut new_x = 0;
-                    if line_maybe.is_some() {
-                        let line = line_maybe.as_mut().unwrap();
-                        // makes the indentation level the same
-                        // for newline
-                        // todo use search function?
-                        loop {
-                            let chr_maybe = &line[new_x];
-                            if chr_maybe.is_none() {
-                                break;
-                            }
-
-                            let chr = chr_maybe.clone().unwrap();
-                            if chr.chr != '\t' {
-                                break;
-                            }
-
-                            new_line.push(Some(chr));
-
-                            new_x += 1;
-                        }
-                        while line.len() != self.cursor.x {
-                            new_line.push(line.remove(self.cursor.x));
-                        }
+                    // makes the indentation level the same
+                    // for newline
+                    let new_x = self.leading_tabs(cursor.y);
+
+                    self.buffers[b].split_line(cursor.x, cursor.y);
+                    for _ in 0..new_x {
+                        self.buffers[b].insert(0, cursor.y + 1, Char::new('\t'));
                     }
-                    self.buffer.insert_line(self.cursor.y + 1, new_line);
-                    self.move_cursor(new_x, self.cursor.y + 1);
+                    self.shift_views(b, cursor.y + 1, true);
+                    self.move_cursor(new_x, cursor.y + 1);
                 },
                 '\t' => {
-                    // might change this
-                    let tab = TAB_SIZE;
+                    let tab = self.buffers[b].tab_width();
                     for _ in 0..tab {
-                        self.buffer.insert(0, self.cursor.y, Char::new('\t'));
+                        self.buffers[b].insert(0, cursor.y, Char::new('\t'));
                     }
-                    self.move_cursor(self.cursor.x + tab, self.cursor.y);
+                    self.move_cursor(cursor.x + tab, cursor.y);
                 },
                 _ => {
-                    self.buffer.insert(self.cursor.x, self.cursor.y, Char::new(chr));
-                    self.move_cursor(self.cursor.x + 1, self.cursor.y);
+                    self.buffers[b].insert(cursor.x, cursor.y, Char::new(chr));
+                    self.move_cursor(cursor.x + 1, cursor.y);
                 }
             }
         }
     }
 
+    fn cursor_left(&mut self) {
+        let cursor = self.view().cursor;
+        if cursor.x != 0 {
+            self.move_cursor(cursor.x - 1, cursor.y);
+        }
+
+        let x = self.view().cursor.x;
+        self.view_mut().cursor.fake_x = x;
+    }
+
+    fn cursor_right(&mut self) {
+        // an empty buffer has no line to move on
+        let cursor = self.view().cursor;
+        if let Ok(len) = self.buffer().width(cursor.y) {
+            self.move_cursor(cmp::min(cursor.x + 1, len), cursor.y);
+        }
+
+        let x = self.view().cursor.x;
+        self.view_mut().cursor.fake_x = x;
+    }
+
+    // Some(x, y) of the row above or below the cursor
+    // when lines wrap
+    fn wrapped_row(&self, up: bool) -> Option<Pos> {
+        let view = self.view();
+        if view.wrap == Wrap::Off {
+            return None;
+        }
+
+        let cursor = view.cursor;
+        let segments = view.segments(self.buffer(), cursor.y);
+        let i = View::segment_of(&segments, cursor.x);
+        let column = cursor.x - segments[i].start;
+
+        let (y, segments, i) = if up && i != 0 {
+            (cursor.y, segments, i - 1)
+        } else if !up && i + 1 < segments.len() {
+            (cursor.y, segments, i + 1)
+        } else if up && cursor.y != 0 {
+            let segments = view.segments(self.buffer(), cursor.y - 1);
+            let i = segments.len() - 1;
+            (cursor.y - 1, segments, i)
+        } else if !up && cursor.y + 1 < self.buffer().height() {
+            (cursor.y + 1, view.segments(self.buffer(), cursor.y + 1), 0)
+        } else {
+            return Some((cursor.x, cursor.y));
+        };
+
+        // rows that continue keep the cursor off their end
+        let range = &segments[i];
+        let last = if i + 1 < segments.len() {
+            range.end.saturating_sub(1)
+        } else {
+            range.end
+        };
+        Some((cmp::min(range.start + column, last), y))
+    }
+
+    fn cursor_up(&mut self) {
+        if let Some((x, y)) = self.wrapped_row(true) {
+            self.move_cursor(x, y);
+            return;
+        }
+
+        let cursor = self.view().cursor;
+        if cursor.y != 0 {
+            if let Ok(len) = self.buffer().width(cursor.y - 1) {
+                self.move_cursor(cmp::min(cursor.fake_x, len), cursor.y - 1);
+            }
+        }
+    }
+
+    fn cursor_down(&mut self) {
+        if let Some((x, y)) = self.wrapped_row(false) {
+            self.move_cursor(x, y);
+            return;
+        }
+
+        let cursor = self.view().cursor;
+        if let Ok(len) = self.buffer().width(cursor.y + 1) {
+            self.move_cursor(cmp::min(cursor.fake_x, len), cursor.y + 1);
+        }
+    }
+
+    // typed text, listings aren't editable
+    fn insert(&mut self, chr: char) {
+        if self.listing().is_none() {
+            self.handle_str(chr.to_string().as_str());
+        }
+    }
+
+    // picks the line in listings
+    fn newline(&mut self) {
+        if self.listing().is_some() {
+            self.pick();
+        } else {
+            self.handle_str("\n");
+        }
+    }
+
+    fn tab(&mut self) {
+        if self.listing().is_some() {
+            return;
+        }
+
+        if self.buffer().expand_tabs() {
+            let spaces = " ".repeat(self.buffer().tab_width());
+            self.handle_str(spaces.as_str());
+        } else {
+            self.handle_str("\t");
+        }
+    }
+
+    // stored tab chars line y of the focused buffer starts with
+    fn leading_tabs(&self, y: usize) -> usize {
+        let buffer = self.buffer();
+        (0..buffer.line_len(y)).take_while(|x| buffer.get(*x, y).map(|x| x.chr) == Some('\t')).count()
+    }
+
+    fn backspace(&mut self) {
+        if self.listing().is_some() {
+            return;
+        }
+
+        let b = self.current();
+        if !self.buffers[b].check_edit() {
+            return;
+        }
+        let cursor = self.view().cursor;
+        if cursor.x == 0 {
+            if cursor.y != 0 {
+                let new_x = match self.buffer().width(cursor.y - 1) {
+                    Ok(len) => len,
+                    Err(err) => {
+                        self.message = Some(err);
+                        return;
+                    }
+                };
+                let new_y = cursor.y - 1;
+
+                // the indentation doesn't join the line above
+                // tabs after it do
+                for _ in 0..self.leading_tabs(cursor.y) {
+                    self.buffers[b].remove(0, cursor.y);
+                }
+                self.buffers[b].join_line(new_y);
+                self.shift_views(b, cursor.y, false);
+
+                self.move_cursor(new_x, new_y);
+            }
+        } else {
+            let chr_maybe = self.buffers[b].remove(cursor.x - 1, cursor.y);
+            if chr_maybe.is_none() {
+                return;
+            }
+
+            let mut new_x = cursor.x - 1;
+            let chr = chr_maybe.as_ref().unwrap().chr;
+            // tabs after the indentation are single chars
+            let leading = (0..new_x).all(|x| self.buffer().get(x, cursor.y).map(|x| x.chr) == Some('\t'));
+            if chr == '\t' && leading {
+                for _ in 1..self.buffers[b].tab_width() {
+                    self.buffers[b].remove(0, cursor.y);
+                    new_x = new_x.saturating_sub(1);
+                }
+
+            }
+
+            self.move_cursor(new_x, cursor.y);
+        }
+    }
+
+    // keymap mode of the focused view
+    fn mode(&self) -> &'static str {
+        if self.prompt.is_some() {
+            "prompt"
+        } else {
+            self.buffer_mode()
+        }
+    }
+
+    // the mode once the prompt is gone
+    fn buffer_mode(&self) -> &'static str {
+        if self.listing().is_some() {
+            "listing"
+        } else {
+            match self.vim.as_ref().map(|x| x.mode) {
+                Some(VimMode::Normal) => "normal",
+                Some(VimMode::Visual) => "visual",
+                Some(VimMode::Insert) => "insert",
+                None => "edit"
+            }
+        }
+    }
+
+    // modes bindings are looked up in
+    // insert mode falls back to the editing bindings
+    fn modes(mode: &'static str) -> &'static [&'static str] {
+        match mode {
+            "insert" => &["insert", "edit"],
+            "prompt" => &["prompt"],
+            "listing" => &["listing"],
+            "normal" => &["normal"],
+            "visual" => &["visual"],
+            _ => &["edit"]
+        }
+    }
+
+    fn run_command(&mut self, name: &str) {
+        match command::find(name) {
+            Some(command) => (command.run)(self),
+            None => self.message = Some(format!("no command: {}", name))
+        }
+    }
+
+    fn press(&mut self, key: Key) {
+        self.pending.push(key);
+
+        let mode = self.mode();
+        if mode == "insert" {
+            self.vim.as_mut().unwrap().record(key);
+        }
+
+        let filetype = self.buffer().syntax_name();
+        let mut lookup = Lookup::None;
+        for mode in JEdit::modes(mode) {
+            lookup = self.keymap.lookup(&self.pending, mode, filetype);
+            if !matches!(lookup, Lookup::None) {
+                break;
+            }
+        }
+        // word chars join one undo step
+        let mut typed = false;
+        match lookup {
+            Lookup::Command(name) => {
+                self.pending.clear();
+                self.run_command(name.as_str());
+                self.last_command = Some(name);
+            },
+            Lookup::Prefix => {
+                self.message = Some(format!("{}-", keymap::keys_name(&self.pending)));
+            },
+            Lookup::None => {
+                let pending = std::mem::take(&mut self.pending);
+
+                self.last_command = None;
+
+                // unbound chars are typed
+                // and answer questions right away
+                match (pending.as_slice(), self.prompt.as_mut()) {
+                    _ if mode == "normal" || mode == "visual" => {
+                        for key in pending {
+                            self.vim_key(key);
+                        }
+                    },
+                    ([Key::Char(chr)], Some(prompt)) if [Ask::Close, Ask::Quit, Ask::Pipe, Ask::Recover, Ask::Changed].contains(&prompt.ask) => {
+                        prompt.insert(*chr);
+                        self.prompt_accept();
+                    },
+                    ([Key::Char(chr)], Some(prompt)) => prompt.insert(*chr),
+                    ([Key::Char(chr)], None) => {
+                        self.insert(*chr);
+                        typed = chr.is_alphanumeric() || *chr == '_';
+                    },
+                    ([_], _) => (),
+                    _ => self.message = Some(format!("{} is undefined", keymap::keys_name(&pending)))
+                }
+            }
+        }
+
+        self.isearch_update();
+        self.sync_region();
+        self.record_edits(typed);
+
+        let current = self.current();
+        if self.buffers[current].take_refused() {
+            self.message = Some(format!("{} is read-only", self.buffers[current].name()));
+        }
+    }
+
     fn run(&mut self) {
-        self.win.set_bg(0);
-        self.win.set_fg(1);
+        self.win.set_bg(theme::BACKGROUND);
+        self.win.set_fg(theme::FOREGROUND);
 
         loop {
             match self.win.poll() {
@@ -243,163 +1522,349 @@ impl JEdit {
                 Some(Event::Redraw(w, h)) => {
                     self.width = w;
                     self.height = h;
-                    self.buffer_height = h;
 
                     self.redraw();
                 },
                 // key events
-                Some(Event::Key(Code::Showable(string))) => {
-                    self.handle_str(&string);
+                Some(Event::Keys(keys)) => {
+                    self.message = None;
+                    for key in keys {
+                        self.press(key);
+                    }
 
+                    if self.quit {
+                        break;
+                    }
                     self.redraw();
                 },
 
-                // special key
-                Some(Event::Key(Code::Backspace)) => {
-                    if self.cursor.x == 0 {
-                        if self.cursor.y != 0 {
-                            let len = self.buffer.width(self.cursor.y - 1);
-                            let new_x = len;
-                            let new_y = self.cursor.y - 1;
-
-                            let curr_line_maybe = self.buffer.remove_line(self.cursor.y);
-                            let new_line_maybe = self.buffer.line_mut(new_y);
-
-                            if new_line_maybe.is_none() {
-                                *new_line_maybe = Some(JVec::new());
-                            }
-
-                            let new_line = new_line_maybe.as_mut().unwrap();
-
-                            if curr_line_maybe.is_some() {
-                                let curr_line = curr_line_maybe.unwrap();
-                                for chr_maybe in curr_line {
-                                    if chr_maybe.is_none() {
-                                        new_line.push(chr_maybe);
-                                    } else {
-                                        let chr = chr_maybe.as_ref().unwrap();
-                                        if chr.chr != '\t' {
-                                            new_line.push(chr_maybe);
-                                        }
-                                    }
-                                }
-                            }
-
-                            self.move_cursor(new_x, new_y);
-                        }
-                    } else {
-                        let chr_maybe = self.buffer.remove(self.cursor.x - 1, self.cursor.y);
-                        if chr_maybe.is_none() {
-                            return;
-                        }
+                Some(Event::Close) => {
+                    self.request_quit();
+                    if self.quit {
+                        break;
+                    }
+                    self.redraw();
+                },
+                None => ()
+            }
 
-                        let mut new_x = self.cursor.x - 1;
-                        let chr = chr_maybe.as_ref().unwrap().chr;
-                        if chr == '\t' {
-                            for _ in 1..TAB_SIZE {
-                                self.buffer.remove(0, self.cursor.y);
+            self.autosave(false);
+            self.check_disk();
+            self.record_edits(false);
 
-                                if new_x != 0 {
-                                    new_x -= 1;
-                                }
-                            }
+            // large files get indexed a step at a time
+            let mut indexed = false;
+            for buffer in self.buffers.iter_mut() {
+                indexed |= buffer.index_more();
+            }
+            if indexed {
+                self.redraw();
+            }
+        }
 
-                        }
+        // unsaved changes were thrown away on purpose
+        // swap files of other sessions and ignored ones stay
+        for path in self.swapped.iter() {
+            swap::remove(path);
+        }
 
-                        self.move_cursor(new_x, self.cursor.y);
-                    }
+        // only the confirmed stdin buffer goes to stdout
+        // so jedit can sit in a pipe
+        if let Some(bytes) = self.output.take() {
+            let mut stdout = io::stdout();
+            if let Err(err) = stdout.write_all(bytes.as_slice()).and_then(|_| stdout.flush()) {
+                eprintln!("couldn't write to stdout: {}", err);
+            }
+        }
+    }
+}
 
-                    self.redraw();
-                },
+// full path of a file that may not exist
+// in a dir that does
+fn absolute(path: &Path) -> io::Result<PathBuf> {
+    let name = path.file_name().ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "not a file name"))?;
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new(".")
+    };
 
-                // moving the cursor with arrow keys
-                Some(Event::Key(Code::Left)) => {
-                    if self.cursor.x != 0 {
-                        self.move_cursor(self.cursor.x - 1, self.cursor.y);
+    Ok(fs::canonicalize(dir)?.join(name))
+}
 
-                        self.redraw();
-                    }
-                    
-                    self.cursor.fake_x = self.cursor.x;
-                },
-                Some(Event::Key(Code::Right)) => {
-                    let len = self.buffer.width(self.cursor.y);
-                    self.move_cursor(cmp::min(self.cursor.x + 1, len), self.cursor.y);
+// a window, or the terminal when asked
+// or when there is no window system
+#[cfg(unix)]
+fn open_display(tty: bool) -> Result<Box<dyn Display>, String> {
+    if !tty {
+        if let Ok(win) = Win::new(String::from("jedit")) {
+            return Ok(Box::new(win));
+        }
+    }
 
-                    self.cursor.fake_x = self.cursor.x;
+    match tty::Tty::new() {
+        Ok(tty) => Ok(Box::new(tty)),
+        Err(err) => Err(format!("couldn't open a window or the terminal: {}", err))
+    }
+}
 
-                    self.redraw();
-                },
-                Some(Event::Key(Code::Up)) => {
-                    if self.cursor.y != 0 {
-                        let len = self.buffer.width(self.cursor.y - 1);
-                        self.move_cursor(cmp::min(self.cursor.fake_x, len), self.cursor.y - 1);
+#[cfg(not(unix))]
+fn open_display(tty: bool) -> Result<Box<dyn Display>, String> {
+    if tty {
+        return Err(String::from("--tty only works on unix"));
+    }
 
-                        self.redraw();
-                    }
-                },
-                Some(Event::Key(Code::Down)) => {
-                    if self.buffer.height() > self.cursor.y + 1 {
-                        let len = self.buffer.width(self.cursor.y + 1);
-                        self.move_cursor(cmp::min(self.cursor.fake_x, len), cmp::min(self.cursor.y + 1, self.buffer.height()));
+    match Win::new(String::from("jedit")) {
+        Ok(win) => Ok(Box::new(win)),
+        Err(err) => Err(format!("couldn't open a window: {}", err))
+    }
+}
 
-                        self.redraw();
-                    }
-                }
+// message of a panic in run, printed once the
+// terminal is back instead of into the alternate screen
+static PANIC: Mutex<Option<String>> = Mutex::new(None);
 
-                Some(Event::Close) => break,
-                _ => ()
-            }
+// without unwinding a panic would lose every unsaved edit
+#[cfg(panic = "abort")]
+compile_error!("jedit writes swap files while unwinding from a panic and needs panic = \"unwind\"");
+
+fn main() {
+    let args = match Args::parse(env::args().skip(1)) {
+        Ok(args) => args,
+        Err(err) => {
+            eprintln!("jedit: {}\n{}", err, args::USAGE);
+            process::exit(2);
         }
+    };
+    if args.help {
+        println!("{}", args::USAGE);
+        return;
+    }
+    if args.version {
+        println!("jedit {}", env!("CARGO_PKG_VERSION"));
+        return;
+    }
 
-        for line in self.buffer.iter() {
-            match line {
-                Some(line) => {
-                    let string: String = line.iter().map(|x| x.as_ref().unwrap().chr).collect();
-                    let str_string: &str = string.as_str();
-                    let string = str_string.replace("\t".repeat(TAB_SIZE).as_str(), "\t");
-                    println!("{}", string);
-                },
-                None => {
-                    println!();
+    let win = match open_display(args.tty) {
+        Ok(win) => win,
+        Err(err) => {
+            eprintln!("{}", err);
+            process::exit(1);
+        }
+    };
+
+    let mut jedit = JEdit::new(win);
+    // later messages would replace earlier ones
+    // so everything from startup is shown at once
+    let mut messages = Vec::new();
+
+    // keymap.toml sits next to the config file
+    // and goes on top of the keymap setting
+    let dir_maybe = conf::dir();
+    let keymap_maybe = match args.config.as_ref() {
+        Some(path) => Some(path.with_file_name("keymap.toml")),
+        None => dir_maybe.as_ref().map(|x| x.join("keymap.toml"))
+    };
+    let config_maybe = match args.config {
+        // only the default config may be missing
+        Some(path) if !path.exists() => {
+            messages.push(format!("no config file {}", path.display()));
+            None
+        },
+        Some(path) => Some(path),
+        None => dir_maybe.map(|x| x.join("config.toml"))
+    };
+    if let Some(config) = config_maybe {
+        if let Err(err) = jedit.settings.load(&config) {
+            messages.push(err);
+        }
+    }
+    if let Some(theme) = args.theme {
+        jedit.settings.theme = theme;
+    }
+    jedit.use_settings();
+    messages.extend(jedit.message.take());
+    if let Some(keymap) = keymap_maybe {
+        if let Err(err) = jedit.keymap.load(&keymap) {
+            messages.push(err);
+        }
+    }
+    for file in args.files.iter() {
+        let syntax_maybe = args.syntax.as_deref();
+        let res = match file.path.as_str() {
+            "-" => jedit.open_stdin(syntax_maybe, args.encoding),
+            path => jedit.open_with(Path::new(path), syntax_maybe, args.encoding)
+        };
+        match res {
+            Ok(()) => {
+                if let Some(line) = file.line {
+                    jedit.go_to(file.col.unwrap_or(1) - 1, line - 1);
+                }
+                if args.readonly {
+                    let current = jedit.current();
+                    jedit.buffers[current].set_readonly(true);
                 }
+            },
+            Err(err) => jedit.message = Some(format!("couldn't open {}: {}", file.path, err))
+        }
+        messages.extend(jedit.message.take());
+    }
+    if !messages.is_empty() {
+        jedit.message = Some(messages.join("; "));
+    }
+
+    if jedit.buffers.is_empty() {
+        let scratch = jedit.scratch();
+        jedit.add_buffer(scratch, "", None);
+    }
+    jedit.switch(0);
+
+    // edits are kept in swap files when anything panics
+    // this happens after unwinding out of run and not in a panic
+    // hook, which couldn't get at the buffers while run has them
+    // jedit has one thread and autosave only reads the buffers
+    // so at worst a swap file holds an edit half done
+    let hook = panic::take_hook();
+    panic::set_hook(Box::new(|info| {
+        let payload = info.payload();
+        let text = match (payload.downcast_ref::<&str>(), payload.downcast_ref::<String>()) {
+            (Some(text), _) => text,
+            (_, Some(text)) => text.as_str(),
+            _ => "Box<dyn Any>"
+        };
+        let message = match info.location() {
+            Some(location) => format!("jedit panicked at {}: {}", location, text),
+            None => format!("jedit panicked: {}", text)
+        };
+        if let Ok(mut panic) = PANIC.lock() {
+            *panic = Some(message);
+        }
+    }));
+    let res = panic::catch_unwind(AssertUnwindSafe(|| jedit.run()));
+    panic::set_hook(hook);
+    if let Err(err) = res {
+        let written = jedit.autosave(true);
+        // puts the terminal back first
+        drop(jedit);
+        if let Some(message) = PANIC.lock().ok().and_then(|mut x| x.take()) {
+            eprintln!("{}", message);
+        }
+        if !written.is_empty() {
+            eprintln!("unsaved changes were written to");
+            for path in written {
+                eprintln!("  {}", path.display());
             }
         }
+        panic::resume_unwind(err);
     }
 }
 
-fn main() {
-    let mut jedit = JEdit {
-        win: Win::new(String::from("jedit")).unwrap(),
-
-        buffer: Buffer::new(Some(Box::new(syntax::Rust::new()))),
-        width: 0, height: 0,
-        buffer_width: 0, buffer_height: 0,
-
-        cursor: Cursor::new(),
-
-        view_x: 0,
-        view_y: 0,
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::display::Headless;
+
+    // an editor with one scratch buffer holding text
+    pub(crate) fn editor(text: &str) -> JEdit {
+        let mut jedit = JEdit::new(Box::new(Headless));
+        let scratch = jedit.scratch();
+        jedit.add_buffer(scratch, text, None);
+        jedit
+    }
 
-        offset_x: 0
-    };
+    #[test]
+    fn replacing_text_clamps_other_views() {
+        let mut jedit = editor("a\nb\nc\nd\ne");
+        jedit.split(Dir::Vertical);
+        jedit.move_cursor(1, 4);
+        jedit.view_mut().view_y = 3;
+        jedit.cycle_focus();
+
+        jedit.replace_text("xy");
+        assert_eq!((jedit.views[1].cursor.x, jedit.views[1].cursor.y), (1, 0));
+        assert_eq!(jedit.views[1].view_y, 0);
+    }
 
-    let path_maybe = env::args().nth(1);
-    if path_maybe.is_none() {
-        jedit.run();
+    #[test]
+    fn undo_clamps_other_views() {
+        let mut jedit = editor("a");
+        jedit.replace_text("a\nb\nc");
+        jedit.record_edits(false);
+        jedit.split(Dir::Vertical);
+        jedit.move_cursor(0, 2);
+        jedit.cycle_focus();
+
+        jedit.undo();
+        assert_eq!(jedit.buffer().text(), "a");
+        assert_eq!(jedit.views[1].cursor.y, 0);
+    }
 
-        return;
+    #[test]
+    fn up_and_down_go_by_rows_when_lines_wrap() {
+        let mut jedit = editor("abcdefghij\nxy");
+        jedit.view_mut().wrap = Wrap::Char;
+        jedit.view_mut().buffer_width = 4;
+        jedit.move_cursor(1, 0);
+
+        let mut rows = Vec::new();
+        for _ in 0..3 {
+            jedit.cursor_down();
+            rows.push((jedit.view().cursor.x, jedit.view().cursor.y));
+        }
+        assert_eq!(rows, vec![(5, 0), (9, 0), (1, 1)]);
+
+        jedit.cursor_up();
+        assert_eq!((jedit.view().cursor.x, jedit.view().cursor.y), (9, 0));
+        // rows that continue keep the cursor off their end
+        jedit.move_cursor(10, 0);
+        jedit.cursor_up();
+        assert_eq!(jedit.view().cursor.x, 6);
+        jedit.move_cursor(2, 1);
+        jedit.cursor_up();
+        assert_eq!((jedit.view().cursor.x, jedit.view().cursor.y), (10, 0));
     }
-    let path = path_maybe.unwrap();
 
-    let file_maybe = File::open(path);
-    assert!(file_maybe.is_ok(), "invalid file");
-    let mut file = file_maybe.ok().unwrap();
-    let mut string = String::new();
-    let res = file.read_to_string(&mut string);
-    assert!(res.is_ok(), "couldn't read from file");
+    #[test]
+    fn cancelled_recoveries_ask_about_the_rest() {
+        let mut jedit = editor("");
+        for name in ["a", "b", "c"] {
+            jedit.recoveries.push((PathBuf::from(name), String::new()));
+        }
+        jedit.next_recovery();
+
+        jedit.prompt_cancel();
+        assert!(jedit.prompt.as_ref().is_some_and(|x| x.ask == Ask::Recover));
+        assert_eq!(jedit.recoveries[0].0, PathBuf::from("b"));
+
+        // a question asked in between
+        jedit.ask(Ask::Line, "line: ", "");
+        jedit.prompt_cancel();
+        assert!(jedit.prompt.as_ref().is_some_and(|x| x.ask == Ask::Recover));
+
+        jedit.press(Key::Char('i'));
+        assert_eq!(jedit.recoveries[0].0, PathBuf::from("c"));
+        jedit.press(Key::Char('i'));
+        assert!(jedit.recoveries.is_empty());
+        assert!(jedit.prompt.is_none());
+    }
 
-    jedit.set_str(string.as_str());
+    #[test]
+    fn backspace_joins_without_the_indentation() {
+        let mut jedit = editor("a\n\tb\tc");
+        jedit.move_cursor(0, 1);
+        jedit.backspace();
+        assert_eq!(jedit.buffer().text(), "ab\tc");
+        assert_eq!((jedit.view().cursor.x, jedit.view().cursor.y), (1, 0));
+    }
 
-    jedit.run();
+    #[test]
+    fn large_files_are_not_reopened() {
+        let path = std::env::temp_dir().join(format!("jedit-reopen-{}", std::process::id()));
+        fs::write(&path, "a\nb\n").unwrap();
+        let mut jedit = editor("");
+        jedit.open_large(path.clone()).unwrap();
+        jedit.reopen("utf-8");
+        let _ = fs::remove_file(&path);
+        assert!(jedit.buffer().is_large());
+        assert_eq!(jedit.message.as_deref(), Some("large files can't be reopened"));
+    }
 }
@@ -0,0 +1,161 @@
+// line based three way merge
+
+// bigger middles of a diff get no matches
+// instead of a huge table
+const LCS_LIMIT: usize = 16_000_000;
+
+// for each line of a the line of b it matches
+// from the longest common subsequence
+fn matches(a: &[&str], b: &[&str]) -> Vec<Option<usize>> {
+    let mut matched = vec![None; a.len()];
+
+    // edits are usually in one place so the
+    // common start and end are cut off first
+    let mut prefix = 0;
+    while prefix < a.len() && prefix < b.len() && a[prefix] == b[prefix] {
+        matched[prefix] = Some(prefix);
+        prefix += 1;
+    }
+    let mut suffix = 0;
+    while suffix < a.len() - prefix && suffix < b.len() - prefix && a[a.len() - 1 - suffix] == b[b.len() - 1 - suffix] {
+        matched[a.len() - 1 - suffix] = Some(b.len() - 1 - suffix);
+        suffix += 1;
+    }
+
+    let a_mid = &a[prefix..a.len() - suffix];
+    let b_mid = &b[prefix..b.len() - suffix];
+    let (n, m) = (a_mid.len(), b_mid.len());
+    if n == 0 || m == 0 || n * m > LCS_LIMIT {
+        return matched;
+    }
+
+    // lengths[i][j] is the lcs of a_mid[i..] and b_mid[j..]
+    let mut lengths = vec![0u32; (n + 1) * (m + 1)];
+    for i in (0..n).rev() {
+        for j in (0..m).rev() {
+            lengths[i * (m + 1) + j] = if a_mid[i] == b_mid[j] {
+                lengths[(i + 1) * (m + 1) + j + 1] + 1
+            } else {
+                lengths[(i + 1) * (m + 1) + j].max(lengths[i * (m + 1) + j + 1])
+            };
+        }
+    }
+
+    let (mut i, mut j) = (0, 0);
+    while i < n && j < m {
+        if a_mid[i] == b_mid[j] {
+            matched[prefix + i] = Some(prefix + j);
+            i += 1;
+            j += 1;
+        } else if lengths[(i + 1) * (m + 1) + j] >= lengths[i * (m + 1) + j + 1] {
+            i += 1;
+        } else {
+            j += 1;
+        }
+    }
+
+    matched
+}
+
+// merges the changes from base to ours and from base to theirs
+// changes to the same lines get conflict markers
+// returns the text and the number of conflicts
+pub fn merge(base: &str, ours: &str, theirs: &str) -> (String, usize) {
+    let base: Vec<&str> = base.split('\n').collect();
+    let ours: Vec<&str> = ours.split('\n').collect();
+    let theirs: Vec<&str> = theirs.split('\n').collect();
+    let to_ours = matches(&base, &ours);
+    let to_theirs = matches(&base, &theirs);
+
+    let mut merged: Vec<&str> = Vec::new();
+    let mut conflicts = 0;
+    let (mut i, mut j, mut k) = (0, 0, 0);
+    loop {
+        // the next base line both sides kept
+        let stable = (i..base.len()).find(|x| to_ours[*x].is_some() && to_theirs[*x].is_some());
+        let (i2, j2, k2) = match stable {
+            Some(x) => (x, to_ours[x].unwrap(), to_theirs[x].unwrap()),
+            None => (base.len(), ours.len(), theirs.len())
+        };
+
+        let (base_chunk, ours_chunk, theirs_chunk) = (&base[i..i2], &ours[j..j2], &theirs[k..k2]);
+        if ours_chunk == base_chunk || ours_chunk == theirs_chunk {
+            merged.extend(theirs_chunk);
+        } else if theirs_chunk == base_chunk {
+            merged.extend(ours_chunk);
+        } else {
+            conflicts += 1;
+            merged.push("<<<<<<< buffer");
+            merged.extend(ours_chunk);
+            merged.push("=======");
+            merged.extend(theirs_chunk);
+            merged.push(">>>>>>> disk");
+        }
+
+        match stable {
+            Some(x) => {
+                merged.push(base[x]);
+                i = i2 + 1;
+                j = j2 + 1;
+                k = k2 + 1;
+            },
+            None => break
+        }
+    }
+
+    (merged.join("\n"), conflicts)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn disjoint_edits() {
+        let merged = merge("a\nb\nc\nd\ne", "A\nb\nc\nd\ne", "a\nb\nc\nd\nE");
+        assert_eq!(merged, (String::from("A\nb\nc\nd\nE"), 0));
+
+        // a line added on one side and removed on the other
+        let merged = merge("a\nb\nc\nd", "a\nnew\nb\nc\nd", "a\nb\nc");
+        assert_eq!(merged, (String::from("a\nnew\nb\nc"), 0));
+    }
+
+    #[test]
+    fn same_edit_on_both_sides() {
+        let merged = merge("a\nb\nc", "a\nB\nc\nd", "a\nB\nc\nd");
+        assert_eq!(merged, (String::from("a\nB\nc\nd"), 0));
+    }
+
+    #[test]
+    fn conflicts() {
+        let merged = merge("a\nb\nc", "a\nX\nc", "a\nY\nc");
+        assert_eq!(merged, (String::from("a\n<<<<<<< buffer\nX\n=======\nY\n>>>>>>> disk\nc"), 1));
+
+        // touching edits conflict like they do in diff3
+        let merged = merge("a\nb\nc", "A\nb\nc", "a\nB\nc");
+        assert_eq!(merged.1, 1);
+
+        let merged = merge("a\nb\nc\nd\ne", "X\nb\nc\nd\nX", "Y\nb\nc\nd\nY");
+        assert_eq!(merged.1, 2);
+    }
+
+    #[test]
+    fn empty_texts() {
+        assert_eq!(merge("", "", ""), (String::new(), 0));
+        assert_eq!(merge("", "a", ""), (String::from("a"), 0));
+        assert_eq!(merge("", "", "b"), (String::from("b"), 0));
+        assert_eq!(merge("", "a", "b").1, 1);
+
+        // everything deleted on one side
+        assert_eq!(merge("a\nb", "", "a\nb"), (String::new(), 0));
+        assert_eq!(merge("a\nb", "a\nb", ""), (String::new(), 0));
+        assert_eq!(merge("a\nb", "", "a\nb\nc").1, 1);
+    }
+
+    #[test]
+    fn unchanged_sides() {
+        assert_eq!(merge("a\nb", "a\nb", "a\nb"), (String::from("a\nb"), 0));
+        assert_eq!(merge("a\nb", "a\nb", "a\nc"), (String::from("a\nc"), 0));
+        assert_eq!(merge("a\nb", "a\nc", "a\nb"), (String::from("a\nc"), 0));
+    }
+}
@@ -0,0 +1,239 @@
+use std::fs;
+use std::path::Path;
+
+use crate::display::Display;
+use crate::theme;
+
+// what the answer of a prompt is for
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Ask {
+    Open,
+    Line,
+    Search,
+    // moves to matches while typing
+    ISearch,
+    Grep,
+    Command,
+    Theme,
+    // reopen the file in another encoding
+    Encoding,
+    // save, discard or cancel
+    Close,
+    Quit,
+    // write the stdin buffer to stdout on quit
+    Pipe,
+    // recover, delete or ignore a swap file
+    Recover,
+    // merge, reload or keep a file changed on disk
+    Changed,
+    // vim : commands
+    Ex
+}
+
+// single line input at the bottom of the window
+pub struct Prompt {
+    pub ask: Ask,
+    label: String,
+
+    text: Vec<char>,
+    cursor: usize,
+
+    // older entries first
+    history: Vec<String>,
+    // history.len() while editing a new entry
+    history_i: usize,
+
+    // Some while tab cycles through candidates
+    cycle: Option<(Vec<String>, usize)>,
+
+    // picked row of a list shown with the prompt
+    // 0 is the best match
+    selected: usize
+}
+
+impl Prompt {
+    pub fn new(ask: Ask, label: &str, text: &str, history: Vec<String>) -> Prompt {
+        let text: Vec<char> = text.chars().collect();
+        Prompt {
+            ask,
+            label: String::from(label),
+
+            cursor: text.len(),
+            text,
+
+            history_i: history.len(),
+            history,
+
+            cycle: None,
+
+            selected: 0
+        }
+    }
+
+    pub fn text(&self) -> String {
+        self.text.iter().collect()
+    }
+
+    fn set_text(&mut self, string: &str) {
+        self.text = string.chars().collect();
+        self.cursor = self.text.len();
+        self.selected = 0;
+    }
+
+    pub fn selected(&self) -> usize {
+        self.selected
+    }
+
+    // moves the selection by one row of len
+    pub fn select(&mut self, next: bool, len: usize) {
+        if next {
+            if self.selected + 1 < len {
+                self.selected += 1;
+            }
+        } else if self.selected != 0 {
+            self.selected -= 1;
+        }
+    }
+
+    pub fn insert(&mut self, chr: char) {
+        self.text.insert(self.cursor, chr);
+        self.cursor += 1;
+        self.cycle = None;
+        self.selected = 0;
+    }
+
+    pub fn backspace(&mut self) {
+        if self.cursor != 0 {
+            self.cursor -= 1;
+            self.text.remove(self.cursor);
+        }
+        self.cycle = None;
+        self.selected = 0;
+    }
+
+    pub fn left(&mut self) {
+        if self.cursor != 0 {
+            self.cursor -= 1;
+        }
+    }
+
+    pub fn right(&mut self) {
+        if self.cursor < self.text.len() {
+            self.cursor += 1;
+        }
+    }
+
+    pub fn home(&mut self) {
+        self.cursor = 0;
+    }
+
+    pub fn end(&mut self) {
+        self.cursor = self.text.len();
+    }
+
+    pub fn history_prev(&mut self) {
+        if self.history_i != 0 {
+            self.history_i -= 1;
+            let entry = self.history[self.history_i].clone();
+            self.set_text(entry.as_str());
+        }
+        self.cycle = None;
+    }
+
+    pub fn history_next(&mut self) {
+        if self.history_i < self.history.len() {
+            self.history_i += 1;
+            let entry = match self.history.get(self.history_i) {
+                Some(entry) => entry.clone(),
+                None => String::new()
+            };
+            self.set_text(entry.as_str());
+        }
+        self.cycle = None;
+    }
+
+    // candidates replace the whole text
+    // the first tab extends to their common prefix
+    // and later ones cycle through them
+    pub fn complete(&mut self, candidates: Vec<String>) {
+        if let Some((candidates, i)) = self.cycle.as_mut() {
+            *i = (*i + 1) % candidates.len();
+            let candidate = candidates[*i].clone();
+            self.set_text(candidate.as_str());
+            return;
+        }
+
+        if candidates.is_empty() {
+            return;
+        }
+
+        let prefix = common_prefix(&candidates);
+        if prefix.chars().count() > self.text.len() || candidates.len() == 1 {
+            self.set_text(prefix.as_str());
+            return;
+        }
+
+        self.set_text(candidates[0].as_str());
+        self.cycle = Some((candidates, 0));
+    }
+
+    pub fn draw(&self, win: &mut dyn Display, y: usize, width: usize) {
+        let mut line = format!("{}: ", self.label);
+        let cursor_x = line.chars().count() + self.cursor;
+        line.extend(self.text.iter());
+        let line: String = line.chars().chain(std::iter::repeat(' ')).take(width).collect();
+
+        win.set_bg(theme::BACKGROUND);
+        win.set_fg(theme::FOREGROUND);
+        win.set_font(theme::FONT_REGULAR);
+        win.put_str(0, y, line.as_str());
+
+        if cursor_x < width {
+            win.set_font(theme::FONT_BOLD);
+            win.put_cursor(cursor_x, y);
+        }
+    }
+}
+
+fn common_prefix(strings: &[String]) -> String {
+    let mut prefix: Vec<char> = strings[0].chars().collect();
+    for string in strings.iter().skip(1) {
+        let len = prefix.iter().zip(string.chars()).take_while(|(a, b)| **a == *b).count();
+        prefix.truncate(len);
+    }
+
+    prefix.into_iter().collect()
+}
+
+// entries of the directory in text
+// that start with its last component
+pub fn complete_path(text: &str) -> Vec<String> {
+    let (dir, prefix) = match text.rfind('/') {
+        Some(i) => (&text[..=i], &text[i + 1..]),
+        None => ("", text)
+    };
+    let read_dir = if dir.is_empty() {
+        Path::new(".")
+    } else {
+        Path::new(dir)
+    };
+
+    let mut candidates = Vec::new();
+    if let Ok(entries) = fs::read_dir(read_dir) {
+        for entry in entries.filter_map(|x| x.ok()) {
+            let name = entry.file_name().to_string_lossy().into_owned();
+            if !name.starts_with(prefix) {
+                continue;
+            }
+
+            let mut candidate = format!("{}{}", dir, name);
+            if entry.path().is_dir() {
+                candidate.push('/');
+            }
+            candidates.push(candidate);
+        }
+    }
+    candidates.sort();
+
+    candidates
+}
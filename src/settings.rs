@@ -0,0 +1,199 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use crate::buffer::Buffer;
+use crate::conf::{self, Entry};
+use crate::syntax::{self, Syntax};
+use crate::view::Wrap;
+use crate::TAB_SIZE;
+
+// tab settings that differ for one syntax
+#[derive(Clone, Default)]
+struct Language {
+    tab_width: Option<usize>,
+    expand_tabs: Option<bool>
+}
+
+// editor settings from config.toml
+pub struct Settings {
+    pub tab_width: usize,
+    // tab inserts spaces
+    pub expand_tabs: bool,
+
+    // name of a built in theme or one in the themes dir
+    pub theme: String,
+    // "default", "emacs" or "vim"
+    pub keymap: String,
+    pub line_numbers: bool,
+    pub wrap: Wrap,
+    // lines and chars kept between the cursor and the edges of a view
+    // None keeps the cursor in the middle half
+    pub scroll_margin: Option<usize>,
+    pub side_scroll_margin: Option<usize>,
+    // seconds between swap file writes, 0 turns them off
+    pub swap_interval: u64,
+    // megabytes above which files open in large file mode
+    // read-only and without highlighting, 0 turns it off
+    pub large_file_size: u64,
+
+    // file extension to syntax name
+    syntaxes: HashMap<String, String>,
+    // syntax name to overrides
+    languages: HashMap<String, Language>
+}
+
+fn tab_width(entry: &Entry) -> Result<usize, String> {
+    match entry.int()? {
+        width @ 1..=16 => Ok(width as usize),
+        _ => Err(entry.error("must be between 1 and 16"))
+    }
+}
+
+fn margin(entry: &Entry) -> Result<usize, String> {
+    match entry.int()? {
+        margin @ 0..=100 => Ok(margin as usize),
+        _ => Err(entry.error("must be between 0 and 100"))
+    }
+}
+
+impl Settings {
+    pub fn new() -> Settings {
+        Settings {
+            tab_width: TAB_SIZE,
+            expand_tabs: false,
+
+            theme: String::from("dark"),
+            keymap: String::from("default"),
+            line_numbers: true,
+            wrap: Wrap::Off,
+            scroll_margin: None,
+            side_scroll_margin: None,
+            swap_interval: 10,
+            large_file_size: 64,
+
+            syntaxes: HashMap::new(),
+            languages: HashMap::new()
+        }
+    }
+
+    // top level keys are global
+    // [syntax] maps extensions like md = "plain"
+    // [language.<syntax>] overrides tab settings
+    pub fn load_str(&mut self, text: &str) -> Result<(), String> {
+        for entry in conf::parse(text)? {
+            let path: Vec<&str> = entry.path.iter().map(|x| x.as_str()).collect();
+            match path.as_slice() {
+                ["tab_width"] => self.tab_width = tab_width(&entry)?,
+                ["expand_tabs"] => self.expand_tabs = entry.bool()?,
+                ["theme"] => self.theme = String::from(entry.str()?),
+                ["keymap"] => match entry.str()? {
+                    keymap @ ("default" | "emacs" | "vim") => self.keymap = String::from(keymap),
+                    _ => return Err(entry.error("expected \"default\", \"emacs\" or \"vim\""))
+                },
+                ["line_numbers"] => self.line_numbers = entry.bool()?,
+                ["wrap"] => self.wrap = match entry.str()? {
+                    "off" => Wrap::Off,
+                    "char" => Wrap::Char,
+                    "word" => Wrap::Word,
+                    _ => return Err(entry.error("expected \"off\", \"char\" or \"word\""))
+                },
+                ["scroll_margin"] => self.scroll_margin = Some(margin(&entry)?),
+                ["side_scroll_margin"] => self.side_scroll_margin = Some(margin(&entry)?),
+                ["swap_interval"] => match entry.int()? {
+                    seconds @ 0..=3600 => self.swap_interval = seconds as u64,
+                    _ => return Err(entry.error("must be between 0 and 3600"))
+                },
+                ["large_file_size"] => match entry.int()? {
+                    megabytes @ 0..=1_000_000 => self.large_file_size = megabytes as u64,
+                    _ => return Err(entry.error("must be between 0 and 1000000"))
+                },
+                ["syntax", extension] => {
+                    let name = entry.str()?;
+                    if syntax::by_name(name).is_none() {
+                        return Err(entry.error(format!("unknown syntax {}", name).as_str()));
+                    }
+                    self.syntaxes.insert(String::from(*extension), String::from(name));
+                },
+                ["language", name, key] => {
+                    if syntax::by_name(name).is_none() {
+                        return Err(entry.error(format!("unknown syntax {}", name).as_str()));
+                    }
+                    let language = self.languages.entry(String::from(*name)).or_default();
+                    match *key {
+                        "tab_width" => language.tab_width = Some(tab_width(&entry)?),
+                        "expand_tabs" => language.expand_tabs = Some(entry.bool()?),
+                        _ => return Err(entry.error("unknown setting"))
+                    }
+                },
+                _ => return Err(entry.error("unknown setting"))
+            }
+        }
+
+        Ok(())
+    }
+
+    // a missing file isn't an error
+    pub fn load(&mut self, path: &Path) -> Result<(), String> {
+        match fs::read_to_string(path) {
+            Ok(text) => self.load_str(text.as_str()).map_err(|x| format!("{}: {}", path.display(), x)),
+            Err(ref err) if err.kind() == io::ErrorKind::NotFound => Ok(()),
+            Err(err) => Err(format!("{}: {}", path.display(), err))
+        }
+    }
+
+    // extension mappings come before the built in ones
+    pub fn syntax_for(&self, path: &Path) -> Box<dyn Syntax> {
+        let mapped = path.extension()
+            .and_then(|x| x.to_str())
+            .and_then(|x| self.syntaxes.get(x))
+            .and_then(|x| syntax::by_name(x));
+        match mapped {
+            Some(syntax) => syntax,
+            None => syntax::for_path(path)
+        }
+    }
+
+    // tab settings for the syntax of buffer
+    // must be applied before text is added
+    pub fn apply(&self, buffer: &mut Buffer) {
+        let language = self.languages.get(buffer.syntax_name()).cloned().unwrap_or_default();
+        buffer.set_tabs(
+            language.tab_width.unwrap_or(self.tab_width),
+            language.expand_tabs.unwrap_or(self.expand_tabs)
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn error(text: &str) -> String {
+        Settings::new().load_str(text).unwrap_err()
+    }
+
+    #[test]
+    fn errors_name_the_line_and_key() {
+        assert_eq!(error("tab_width = 40"), "line 1: tab_width: must be between 1 and 16");
+        assert_eq!(error("\ntheme = 1"), "line 2: theme: expected a string, found an integer");
+        assert_eq!(error("keymap = \"ed\""), "line 1: keymap: expected \"default\", \"emacs\" or \"vim\"");
+        assert_eq!(error("wrap = \"line\""), "line 1: wrap: expected \"off\", \"char\" or \"word\"");
+        assert_eq!(error("swap_interval = -1"), "line 1: swap_interval: must be between 0 and 3600");
+        assert_eq!(error("[syntax]\nmd = \"markdown\""), "line 2: syntax.md: unknown syntax markdown");
+        assert_eq!(error("[language.cobol]\ntab_width = 2"), "line 2: language.cobol.tab_width: unknown syntax cobol");
+        assert_eq!(error("[language.rust]\nindent = 2"), "line 2: language.rust.indent: unknown setting");
+        assert_eq!(error("font = \"mono\""), "line 1: font: unknown setting");
+        assert_eq!(error("expand_tabs = yes"), "line 1: invalid value yes");
+    }
+
+    #[test]
+    fn good_settings_load() {
+        let mut settings = Settings::new();
+        settings.load_str("tab_width = 2\nwrap = \"word\"\n[language.rust]\nexpand_tabs = true").unwrap();
+        assert_eq!(settings.tab_width, 2);
+        assert!(settings.wrap == Wrap::Word);
+        assert_eq!(settings.languages["rust"].expand_tabs, Some(true));
+    }
+}
@@ -0,0 +1,51 @@
+use crate::display::Display;
+
+use crate::buffer::Buffer;
+use crate::theme;
+use crate::view::View;
+
+// bottom line of the window
+// shows the focused buffer and view
+pub fn draw(win: &mut dyn Display, y: usize, width: usize, buffer: &Buffer, view: &View, message: Option<&str>) {
+    let mut left = format!(" {}", buffer.name());
+    if buffer.modified() {
+        left.push_str(" [+]");
+    }
+    if buffer.is_readonly() {
+        left.push_str(" [ro]");
+    }
+    if let Some(message) = message {
+        left.push_str("  ");
+        left.push_str(message);
+    }
+
+    // tabs are stored expanded so x already is the screen column
+    // large files are still being indexed
+    let more = if buffer.is_indexed() {
+        ""
+    } else {
+        "+"
+    };
+    let mut right = format!("{}:{}  {}{} lines  ", view.cursor.y + 1, view.cursor.x + 1, buffer.height(), more);
+    let format = buffer.format().describe();
+    if !format.is_empty() {
+        right.push_str(format.as_str());
+        right.push_str("  ");
+    }
+    right.push_str(buffer.syntax_name());
+    right.push(' ');
+
+    let left_len = left.chars().count();
+    let right_len = right.chars().count();
+    let mut line = left;
+    if left_len + right_len < width {
+        line.push_str(" ".repeat(width - left_len - right_len).as_str());
+        line.push_str(right.as_str());
+    }
+    let line: String = line.chars().chain(std::iter::repeat(' ')).take(width).collect();
+
+    win.set_bg(theme::STATUS_BACKGROUND);
+    win.set_fg(theme::STATUS_FOREGROUND);
+    win.set_font(theme::FONT_BOLD);
+    win.put_str(0, y, line.as_str());
+}
@@ -0,0 +1,58 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::conf;
+
+// recovery copies of modified buffers
+// one file per open file in the state dir
+// named after the full path like vim does
+
+fn dir() -> Option<PathBuf> {
+    Some(conf::state_dir()?.join("swap"))
+}
+
+// /home/a/b.rs is %home%a%b.rs.swp
+fn path_for(file: &Path) -> Option<PathBuf> {
+    let name = file.to_string_lossy().replace(['/', '\\'], "%");
+    Some(dir()?.join(format!("{}.swp", name)))
+}
+
+// returns the path of the swap file
+pub fn write(file: &Path, text: &str) -> io::Result<PathBuf> {
+    let path = path_for(file).ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no state dir"))?;
+    fs::create_dir_all(path.parent().unwrap())?;
+
+    // a crash mid write keeps the old swap file
+    let tmp = path.with_extension("swp.tmp");
+    fs::write(&tmp, text)?;
+    fs::rename(&tmp, &path)?;
+
+    Ok(path)
+}
+
+// a missing swap file is fine
+pub fn remove(file: &Path) {
+    if let Some(path) = path_for(file) {
+        let _ = fs::remove_file(path);
+    }
+}
+
+// text of a swap file that is newer than file
+// and differs from what is in it
+pub fn newer(file: &Path, text: &str) -> Option<String> {
+    let path = path_for(file)?;
+    let swap_time = fs::metadata(&path).ok()?.modified().ok()?;
+    if let Ok(file_time) = fs::metadata(file).and_then(|x| x.modified()) {
+        if file_time > swap_time {
+            return None;
+        }
+    }
+
+    let swap = fs::read_to_string(path).ok()?;
+    if swap == text {
+        return None;
+    }
+
+    Some(swap)
+}
@@ -1,14 +1,35 @@
 use std::ops::Range;
+use std::path::Path;
 
 use crate::buffer::{Char, Buffer};
 
 // divine intellect syntax highlighter
 
 pub trait Syntax {
+    // shown in the status bar
+    fn name(&self) -> &'static str;
+
     fn insert(&mut self, x: usize, y: usize, chr: char, buffer: &mut Buffer);
     fn remove(&mut self, x: usize, y: usize, chr: &Char, buffer: &mut Buffer);
 }
 
+// picked by file extension
+pub fn for_path(path: &Path) -> Box<dyn Syntax> {
+    match path.extension().and_then(|x| x.to_str()) {
+        Some("rs") => Box::new(Rust::new()),
+        _ => Box::new(Plain::new())
+    }
+}
+
+// names used in config files
+pub fn by_name(name: &str) -> Option<Box<dyn Syntax>> {
+    match name {
+        "plain" => Some(Box::new(Plain::new())),
+        "rust" => Some(Box::new(Rust::new())),
+        _ => None
+    }
+}
+
 pub struct Plain;
 
 impl Plain {
@@ -18,13 +39,15 @@ impl Plain {
 }
 
 impl Syntax for Plain {
-    fn insert(&mut self, x: usize, y: usize, chr: char, buffer: &mut Buffer) {
-        buffer.get_mut(x, y).unwrap().fg = 1;
+    fn name(&self) -> &'static str {
+        "plain"
     }
 
-    fn remove(&mut self, x: usize, y: usize, chr: &Char, buffer: &mut Buffer) {
-        return;
+    fn insert(&mut self, x: usize, y: usize, _chr: char, buffer: &mut Buffer) {
+        buffer.get_mut(x, y).unwrap().fg = 1;
     }
+
+    fn remove(&mut self, _x: usize, _y: usize, _chr: &Char, _buffer: &mut Buffer) {}
 }
 
 // Rust syntax
@@ -40,7 +63,7 @@ const COMMENT_0: usize = COMMENT + 8;
 const COMMENT_1: usize = COMMENT + 16;
 
 const KEYWORD: usize = 2;
-const DEFAULT: usize = 1;
+const DEFAULT: usize = IDENTIFIER;
 
 pub struct Rust;
 
@@ -124,9 +147,7 @@ impl Rust {
 
             color_range(0..1, y, color, buffer);
         } else {
-            let left_chr_maybe = buffer.get(x - 1, y);
-            if left_chr_maybe.is_some() {
-                let left_chr = left_chr_maybe.unwrap();
+            if let Some(left_chr) = buffer.get(x - 1, y) {
 
                 color = match left_chr.fg {
                     COMMENT | COMMENT_1 => COMMENT,
@@ -194,8 +215,12 @@ impl Rust {
 }
 
 impl Syntax for Rust {
-    fn insert(&mut self, x: usize, y: usize, chr: char, buffer: &mut Buffer) {
-        let width = buffer.width(y);
+    fn name(&self) -> &'static str {
+        "rust"
+    }
+
+    fn insert(&mut self, x: usize, y: usize, _chr: char, buffer: &mut Buffer) {
+        let width = buffer.line_len(y);
 
         // todo dont use search
         let (range, string) = search(x, y, buffer, |chr| chr == '/');
@@ -211,7 +236,7 @@ impl Syntax for Rust {
     }
 
     fn remove(&mut self, x: usize, y: usize, chr: &Char, buffer: &mut Buffer) {
-        let width = buffer.width(y);
+        let width = buffer.line_len(y);
 
         match chr.fg {
             COMMENT_0 => {
@@ -0,0 +1,401 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+
+use crate::display::{Display, Event};
+
+use crate::conf;
+
+// slots the highlighters and the ui draw with
+// highlighters keep state in slot + 8 and + 16
+// so those get the color of slot
+pub const BACKGROUND: usize = 0;
+pub const FOREGROUND: usize = 1;
+pub const SELECTION: usize = 24;
+pub const CURSOR_LINE: usize = 25;
+pub const GUTTER: usize = 26;
+pub const STATUS_BACKGROUND: usize = 27;
+pub const STATUS_FOREGROUND: usize = 28;
+
+const SLOTS: usize = 29;
+
+// jwin has a fixed palette of eight colors
+// themes pick one of them for each slot
+// by the kind it is the default for
+// or give a "#rrggbb" the terminal can show
+const COLORS: usize = 8;
+
+// about what jwin and the terminal show for the palette
+// black, white, magenta, green, yellow, cyan, red, grey
+const PALETTE_RGB: [(u8, u8, u8); COLORS] = [
+    (0, 0, 0),
+    (229, 229, 229),
+    (205, 0, 205),
+    (0, 205, 0),
+    (205, 205, 0),
+    (0, 205, 205),
+    (205, 0, 0),
+    (127, 127, 127)
+];
+
+// jwin fonts
+pub const FONT_REGULAR: usize = 0;
+pub const FONT_BOLD: usize = 1;
+pub const FONT_ITALIC: usize = 2;
+pub const FONT_BOLD_ITALIC: usize = 3;
+
+// names in the [colors] and [styles] sections of a theme
+const KINDS: &[(&str, usize)] = &[
+    ("background", BACKGROUND),
+    ("foreground", FOREGROUND),
+    ("keyword", 2),
+    ("string", 3),
+    ("type", 4),
+    ("number", 5),
+    ("special", 6),
+    ("comment", 7),
+
+    ("selection", SELECTION),
+    ("cursor_line", CURSOR_LINE),
+    ("gutter", GUTTER),
+    ("status_background", STATUS_BACKGROUND),
+    ("status_foreground", STATUS_FOREGROUND)
+];
+
+// shipped with the editor
+// themes in the config dir with the same name win
+const BUILTIN: &[(&str, &str)] = &[
+    ("dark", include_str!("../themes/dark.toml")),
+    ("light", include_str!("../themes/light.toml")),
+    ("mono-dark", include_str!("../themes/mono-dark.toml")),
+    ("mono-light", include_str!("../themes/mono-light.toml")),
+    ("solarized-dark", include_str!("../themes/solarized-dark.toml")),
+    ("solarized-light", include_str!("../themes/solarized-light.toml"))
+];
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Color {
+    Palette(usize),
+    Rgb(u8, u8, u8)
+}
+
+// "#rrggbb"
+fn parse_rgb(string: &str) -> Option<Color> {
+    let hex = string.strip_prefix('#')?;
+    if hex.len() != 6 || !hex.chars().all(|x| x.is_ascii_hexdigit()) {
+        return None;
+    }
+
+    let channel = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).unwrap();
+    Some(Color::Rgb(channel(0), channel(2), channel(4)))
+}
+
+// palette color closest to r, g, b
+// for displays without true color
+pub fn nearest(r: u8, g: u8, b: u8) -> usize {
+    let distance = |(pr, pg, pb): (u8, u8, u8)| {
+        let d = |x: u8, y: u8| (x as i32 - y as i32).pow(2);
+        d(r, pr) + d(g, pg) + d(b, pb)
+    };
+
+    (0..COLORS).min_by_key(|x| distance(PALETTE_RGB[*x])).unwrap()
+}
+
+// color of each slot without a theme
+fn default_colors() -> [Color; SLOTS] {
+    let mut colors = [Color::Palette(FOREGROUND); SLOTS];
+    for (slot, color) in colors.iter_mut().enumerate() {
+        *color = Color::Palette(match slot {
+            0..=23 => slot % COLORS,
+            SELECTION | GUTTER => 7,
+            CURSOR_LINE | STATUS_FOREGROUND => BACKGROUND,
+            _ => FOREGROUND
+        });
+    }
+
+    colors
+}
+
+fn user_dir() -> Option<PathBuf> {
+    Some(conf::dir()?.join("themes"))
+}
+
+// how a token kind is drawn besides its color
+// underline and strikethrough are terminal only
+// since jwin fonts have no lines to draw them with
+#[derive(Copy, Clone, Default)]
+pub struct Style {
+    bold: bool,
+    italic: bool,
+    underline: bool,
+    strikethrough: bool
+}
+
+impl Style {
+    // "bold italic", "underline" or "none"
+    fn parse(string: &str) -> Option<Style> {
+        let mut style = Style::default();
+        for word in string.split_whitespace() {
+            match word {
+                "bold" => style.bold = true,
+                "italic" => style.italic = true,
+                "underline" => style.underline = true,
+                "strikethrough" => style.strikethrough = true,
+                "none" => (),
+                _ => return None
+            }
+        }
+
+        Some(style)
+    }
+
+    pub fn font(&self) -> usize {
+        match (self.bold, self.italic) {
+            (true, true) => FONT_BOLD_ITALIC,
+            (true, false) => FONT_BOLD,
+            (false, true) => FONT_ITALIC,
+            (false, false) => FONT_REGULAR
+        }
+    }
+
+    // lines drawn with the next chars
+    pub fn decorate(&self, win: &mut dyn Display) {
+        win.set_decoration(self.underline, self.strikethrough);
+    }
+}
+
+// styles of the highlighter kinds
+// by palette index like colors
+pub type Styles = [Style; 8];
+
+pub struct Theme {
+    colors: [Color; SLOTS],
+    pub styles: Styles
+}
+
+impl Theme {
+    // [colors] gives each kind the palette color
+    // of a token kind like "comment", a "#rrggbb"
+    // or a name from [palette] for one
+    // [styles] gives token kinds a font and decorations
+    // which windows leave out
+    pub fn parse(text: &str) -> Result<Theme, String> {
+        let entries = conf::parse(text)?;
+
+        let mut palette = HashMap::new();
+        for entry in entries.iter().filter(|x| x.path[0] == "palette") {
+            if entry.path.len() != 2 {
+                return Err(entry.error("expected palette.<name>"));
+            }
+            let rgb = parse_rgb(entry.str()?).ok_or_else(|| entry.error("expected a color like \"#rrggbb\""))?;
+            palette.insert(entry.path[1].as_str(), rgb);
+        }
+
+        let mut colors_maybe: [Option<Color>; SLOTS] = [None; SLOTS];
+        let mut styles = [Style::default(); 8];
+        for entry in entries.iter().filter(|x| x.path[0] != "palette") {
+            let (section, slot) = match entry.path.as_slice() {
+                [section, kind] if section == "colors" || section == "styles" => KINDS.iter()
+                    .find(|(x, _)| x == kind)
+                    .map(|(_, slot)| (section, *slot))
+                    .ok_or_else(|| entry.error("unknown kind"))?,
+                _ => return Err(entry.error("unknown key"))
+            };
+
+            if section == "styles" {
+                if slot >= styles.len() {
+                    return Err(entry.error("only token kinds have styles"));
+                }
+                styles[slot] = Style::parse(entry.str()?)
+                    .ok_or_else(|| entry.error("expected bold, italic, underline, strikethrough or none"))?;
+                continue;
+            }
+
+            let value = entry.str()?;
+            let color = palette.get(value).copied()
+                .or_else(|| KINDS[..COLORS].iter().find(|(x, _)| *x == value).map(|(_, color)| Color::Palette(*color)))
+                .or_else(|| parse_rgb(value))
+                .ok_or_else(|| entry.error(format!("no palette color {}", value).as_str()))?;
+            colors_maybe[slot] = Some(color);
+        }
+
+        // highlighter state follows its token kind and the
+        // cursor line and status bar follow the background
+        // and foreground unless they are given
+        let mut colors = default_colors();
+        for (slot, color) in colors.iter_mut().enumerate() {
+            let follows = match slot {
+                0..=23 => slot % COLORS,
+                CURSOR_LINE | STATUS_FOREGROUND => BACKGROUND,
+                STATUS_BACKGROUND => FOREGROUND,
+                _ => slot
+            };
+            if let Some(given) = colors_maybe[slot].or(colors_maybe[follows]) {
+                *color = given;
+            }
+        }
+
+        Ok(Theme {
+            colors,
+            styles
+        })
+    }
+
+    // user themes before built in ones
+    pub fn load(name: &str) -> Result<Theme, String> {
+        if let Some(dir) = user_dir() {
+            let path = dir.join(format!("{}.toml", name));
+            if let Ok(text) = fs::read_to_string(&path) {
+                return Theme::parse(text.as_str()).map_err(|x| format!("{}: {}", path.display(), x));
+            }
+        }
+
+        match BUILTIN.iter().find(|(x, _)| *x == name) {
+            Some((_, text)) => Theme::parse(text).map_err(|x| format!("theme {}: {}", name, x)),
+            None => Err(format!("no theme {}", name))
+        }
+    }
+
+    pub fn apply(&self, win: &mut Themed) {
+        win.colors = self.colors;
+    }
+}
+
+// a display drawn with theme slots
+// that gets the colors of the theme
+pub struct Themed {
+    display: Box<dyn Display>,
+    colors: [Color; SLOTS]
+}
+
+impl Themed {
+    pub fn new(display: Box<dyn Display>) -> Themed {
+        Themed {
+            display,
+            colors: default_colors()
+        }
+    }
+
+    fn color(&self, slot: usize) -> Color {
+        self.colors.get(slot).copied().unwrap_or(Color::Palette(slot % COLORS))
+    }
+}
+
+impl Display for Themed {
+    fn poll(&mut self) -> Option<Event> {
+        self.display.poll()
+    }
+
+    fn set_fg(&mut self, color: usize) {
+        match self.color(color) {
+            Color::Palette(color) => self.display.set_fg(color),
+            Color::Rgb(r, g, b) => self.display.set_fg_rgb(r, g, b)
+        }
+    }
+
+    fn set_bg(&mut self, color: usize) {
+        match self.color(color) {
+            Color::Palette(color) => self.display.set_bg(color),
+            Color::Rgb(r, g, b) => self.display.set_bg_rgb(r, g, b)
+        }
+    }
+
+    fn set_font(&mut self, font: usize) {
+        self.display.set_font(font);
+    }
+
+    fn set_decoration(&mut self, underline: bool, strikethrough: bool) {
+        self.display.set_decoration(underline, strikethrough);
+    }
+
+    fn put_char(&mut self, x: usize, y: usize, chr: char) {
+        self.display.put_char(x, y, chr);
+    }
+
+    fn put_str(&mut self, x: usize, y: usize, string: &str) {
+        self.display.put_str(x, y, string);
+    }
+
+    fn put_cursor(&mut self, x: usize, y: usize) {
+        self.display.put_cursor(x, y);
+    }
+
+    fn flush(&mut self) {
+        self.display.flush();
+    }
+}
+
+// built in and user theme names for completion
+pub fn names() -> Vec<String> {
+    let mut names: Vec<String> = BUILTIN.iter().map(|(x, _)| String::from(*x)).collect();
+    if let Some(entries) = user_dir().and_then(|x| fs::read_dir(x).ok()) {
+        for entry in entries.filter_map(|x| x.ok()) {
+            let name = entry.file_name().to_string_lossy().into_owned();
+            if let Some(name) = name.strip_suffix(".toml") {
+                names.push(String::from(name));
+            }
+        }
+    }
+    names.sort();
+    names.dedup();
+
+    names
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn builtin_themes_parse() {
+        for (name, text) in BUILTIN {
+            assert!(Theme::parse(text).is_ok(), "{}", name);
+        }
+    }
+
+    #[test]
+    fn slots_follow_their_kind() {
+        let theme = Theme::parse("[colors]\nbackground = \"foreground\"\nforeground = \"background\"\nkeyword = \"string\"\n").unwrap();
+        assert_eq!(theme.colors[2], Color::Palette(3));
+        // highlighter state
+        assert_eq!(theme.colors[10], Color::Palette(3));
+        assert_eq!(theme.colors[18], Color::Palette(3));
+        assert_eq!(theme.colors[CURSOR_LINE], Color::Palette(FOREGROUND));
+        assert_eq!(theme.colors[STATUS_BACKGROUND], Color::Palette(BACKGROUND));
+        assert_eq!(theme.colors[STATUS_FOREGROUND], Color::Palette(FOREGROUND));
+        assert_eq!(theme.colors[7], Color::Palette(7));
+    }
+
+    #[test]
+    fn rgb_colors() {
+        let theme = Theme::parse("[palette]\nsea = \"#002b36\"\n[colors]\nbackground = \"sea\"\ncomment = \"#FF8000\"\n").unwrap();
+        assert_eq!(theme.colors[BACKGROUND], Color::Rgb(0, 0x2b, 0x36));
+        assert_eq!(theme.colors[CURSOR_LINE], Color::Rgb(0, 0x2b, 0x36));
+        assert_eq!(theme.colors[23], Color::Rgb(0xff, 0x80, 0));
+        assert!(Theme::parse("[palette]\nsea = \"blue\"\n").is_err());
+        assert!(Theme::parse("[colors]\ncomment = \"#12345\"\n").is_err());
+    }
+
+    #[test]
+    fn nearest_palette_colors() {
+        assert_eq!(nearest(0, 0x2b, 0x36), BACKGROUND);
+        assert_eq!(nearest(0xfd, 0xf6, 0xe3), FOREGROUND);
+        assert_eq!(nearest(0x26, 0x8b, 0xd2), 5);
+        assert_eq!(nearest(0xdc, 0x32, 0x2f), 6);
+    }
+
+    #[test]
+    fn empty_theme_is_the_default() {
+        assert_eq!(Theme::parse("").unwrap().colors, default_colors());
+    }
+
+    #[test]
+    fn errors() {
+        assert_eq!(Theme::parse("[colors]\ngutter = \"white\"\n").err().unwrap(), "line 2: colors.gutter: no palette color white");
+        // ui slots aren't palette colors
+        assert!(Theme::parse("[colors]\ngutter = \"selection\"\n").is_err());
+        assert!(Theme::parse("[colors]\nnope = \"comment\"\n").is_err());
+        assert!(Theme::parse("[styles]\ngutter = \"bold\"\n").is_err());
+        assert!(Theme::parse("[styles]\ncomment = \"loud\"\n").is_err());
+    }
+}
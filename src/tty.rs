@@ -0,0 +1,547 @@
+use std::cmp;
+use std::fs::{File, OpenOptions};
+use std::io::{self, Read, Write};
+use std::os::raw::{c_int, c_ulong};
+use std::os::unix::io::AsRawFd;
+use std::process::{Command, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use crate::display::{Display, Event};
+use crate::key::Key;
+use crate::theme::{self, Color};
+
+// a terminal driven by ansi escape codes
+// for ssh and anywhere else without a window
+// it uses /dev/tty so stdin and stdout stay free for pipes
+
+extern "C" {
+    fn signal(signum: c_int, handler: extern "C" fn(c_int)) -> usize;
+    fn ioctl(fd: c_int, request: c_ulong, ...) -> c_int;
+}
+
+// 28 on linux, macos and the bsds but mips linux
+// numbers its signals like irix
+#[cfg(all(target_os = "linux", any(target_arch = "mips", target_arch = "mips64")))]
+const SIGWINCH: c_int = 20;
+#[cfg(not(all(target_os = "linux", any(target_arch = "mips", target_arch = "mips64"))))]
+const SIGWINCH: c_int = 28;
+
+// linux has its own numbers on most architectures
+// the rest use the bsd encoding of _IOR('t', 104, winsize)
+#[cfg(all(
+    any(target_os = "linux", target_os = "android"),
+    not(any(target_arch = "mips", target_arch = "mips64", target_arch = "powerpc", target_arch = "powerpc64", target_arch = "sparc64"))
+))]
+const TIOCGWINSZ: c_ulong = 0x5413;
+#[cfg(not(all(
+    any(target_os = "linux", target_os = "android"),
+    not(any(target_arch = "mips", target_arch = "mips64", target_arch = "powerpc", target_arch = "powerpc64", target_arch = "sparc64"))
+)))]
+const TIOCGWINSZ: c_ulong = 0x40087468;
+
+// struct winsize
+#[repr(C)]
+#[derive(Default)]
+struct WinSize {
+    rows: u16,
+    cols: u16,
+    x_pixels: u16,
+    y_pixels: u16
+}
+
+// foreground codes for the jwin palette colors
+// background codes are 10 more
+// black, white, magenta, green, yellow, cyan, red, grey
+const ANSI: [u8; 8] = [30, 37, 35, 32, 33, 36, 31, 90];
+
+// starts out set for the first redraw
+static RESIZED: AtomicBool = AtomicBool::new(true);
+
+extern "C" fn on_resize(_: c_int) {
+    RESIZED.store(true, Ordering::SeqCst);
+}
+
+// output of stty run on the terminal
+fn stty(tty: &File, args: &[&str]) -> io::Result<String> {
+    let output = Command::new("stty")
+        .args(args)
+        .stdin(Stdio::from(tty.try_clone()?))
+        .stderr(Stdio::piped())
+        .output()?;
+    if !output.status.success() {
+        let err = String::from_utf8_lossy(&output.stderr).trim().to_string();
+        return Err(io::Error::other(format!("stty: {}", err)));
+    }
+
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+// length of the utf-8 char starting with byte
+fn utf8_len(byte: u8) -> usize {
+    match byte {
+        0xc0..=0xdf => 2,
+        0xe0..=0xef => 3,
+        0xf0..=0xf7 => 4,
+        _ => 1
+    }
+}
+
+// east asian wide chars and emoji take two columns
+// the common blocks, not the whole unicode table
+fn wide(chr: char) -> bool {
+    matches!(chr as u32,
+        0x1100..=0x115f | 0x2e80..=0x303e | 0x3041..=0x33ff | 0x3400..=0x4dbf
+        | 0x4e00..=0x9fff | 0xa000..=0xa4cf | 0xac00..=0xd7a3 | 0xf900..=0xfaff
+        | 0xfe30..=0xfe4f | 0xff00..=0xff60 | 0xffe0..=0xffe6 | 0x1f300..=0x1f64f
+        | 0x1f900..=0x1f9ff | 0x20000..=0x2fffd | 0x30000..=0x3fffd)
+}
+
+// keys in bytes read from the terminal and the bytes they used
+// a sequence cut off at the end waits for the next read unless
+// done says nothing more came, then a lone esc is the esc key
+// unknown escape sequences and invalid bytes are dropped
+fn parse_keys(bytes: &[u8], done: bool) -> (Vec<Key>, usize) {
+    let mut keys = Vec::new();
+
+    let mut i = 0;
+    while i < bytes.len() {
+        let rest = &bytes[i..];
+        let (key_maybe, len) = match rest {
+            [b'\x1b', b'[' | b'O', b'A', ..] => (Some(Key::Up), 3),
+            [b'\x1b', b'[' | b'O', b'B', ..] => (Some(Key::Down), 3),
+            [b'\x1b', b'[' | b'O', b'C', ..] => (Some(Key::Right), 3),
+            [b'\x1b', b'[' | b'O', b'D', ..] => (Some(Key::Left), 3),
+            [b'\x1b', b'[', b'1', b';', b'5', b'A', ..] => (Some(Key::CtrlUp), 6),
+            [b'\x1b', b'[', b'1', b';', b'5', b'B', ..] => (Some(Key::CtrlDown), 6),
+            // parameters up to a final byte
+            [b'\x1b', b'[', params @ ..] => match params.iter().position(|x| (0x40..=0x7e).contains(x)) {
+                Some(end) => (None, end + 3),
+                None if done => (None, rest.len()),
+                None => break
+            },
+            [b'\x1b'] | [b'\x1b', b'O'] if !done => break,
+            [b'\x7f', ..] => (Some(Key::Backspace), 1),
+            [byte, ..] => {
+                let len = utf8_len(*byte);
+                if rest.len() < len && !done {
+                    break;
+                }
+                match std::str::from_utf8(&rest[..cmp::min(len, rest.len())]) {
+                    Ok(string) => (string.chars().next().and_then(Key::from_char), len),
+                    Err(_) => (None, 1)
+                }
+            },
+            [] => break
+        };
+
+        keys.extend(key_maybe);
+        i += len;
+    }
+
+    (keys, i)
+}
+
+#[derive(Clone, PartialEq)]
+struct Cell {
+    // a char and its combining chars
+    // empty right of a wide char
+    text: String,
+    fg: Color,
+    bg: Color,
+    font: usize,
+    underline: bool,
+    strikethrough: bool
+}
+
+impl Cell {
+    fn blank() -> Cell {
+        Cell {
+            text: String::from(" "),
+            fg: Color::Palette(theme::FOREGROUND),
+            bg: Color::Palette(theme::BACKGROUND),
+            font: theme::FONT_REGULAR,
+            underline: false,
+            strikethrough: false
+        }
+    }
+}
+
+// sgr sequence for the colors, font and decorations of cell
+fn style(cell: &Cell) -> String {
+    let mut sgr = String::from("\x1b[0");
+    if cell.font == theme::FONT_BOLD || cell.font == theme::FONT_BOLD_ITALIC {
+        sgr.push_str(";1");
+    }
+    if cell.font == theme::FONT_ITALIC || cell.font == theme::FONT_BOLD_ITALIC {
+        sgr.push_str(";3");
+    }
+    if cell.underline {
+        sgr.push_str(";4");
+    }
+    if cell.strikethrough {
+        sgr.push_str(";9");
+    }
+    for (color, offset) in [(cell.fg, 0), (cell.bg, 10)] {
+        match color {
+            Color::Palette(i) => sgr.push_str(format!(";{}", ANSI[i % ANSI.len()] + offset).as_str()),
+            // 24-bit color
+            Color::Rgb(r, g, b) => sgr.push_str(format!(";{};2;{};{};{}", 38 + offset, r, g, b).as_str())
+        }
+    }
+    sgr.push('m');
+
+    sgr
+}
+
+pub struct Tty {
+    tty: File,
+    // stty settings to restore
+    saved: String,
+    // read bytes of an unfinished key
+    pending: Vec<u8>,
+
+    fg: Color,
+    bg: Color,
+    font: usize,
+    underline: bool,
+    strikethrough: bool,
+
+    width: usize,
+    height: usize,
+    // the next frame
+    cells: Vec<Cell>,
+    // what the terminal shows, empty to redraw all of it
+    shown: Vec<Cell>,
+    cursor: Option<(usize, usize)>
+}
+
+impl Tty {
+    pub fn new() -> io::Result<Tty> {
+        let tty = OpenOptions::new().read(true).write(true).open("/dev/tty")?;
+        let saved = stty(&tty, &["-g"])?;
+        // reads give up after a tenth of a second
+        // so resizes and timers get a turn
+        stty(&tty, &["raw", "-echo", "min", "0", "time", "1"])?;
+        unsafe {
+            signal(SIGWINCH, on_resize);
+        }
+
+        let mut tty = Tty {
+            tty,
+            saved,
+            pending: Vec::new(),
+
+            fg: Color::Palette(theme::FOREGROUND),
+            bg: Color::Palette(theme::BACKGROUND),
+            font: theme::FONT_REGULAR,
+            underline: false,
+            strikethrough: false,
+
+            width: 0,
+            height: 0,
+            cells: Vec::new(),
+            shown: Vec::new(),
+            cursor: None
+        };
+        // alternate screen without the terminal cursor
+        tty.tty.write_all(b"\x1b[?1049h\x1b[?25l")?;
+
+        Ok(tty)
+    }
+
+    // columns and rows, some terminals report 0 0
+    fn size(&self) -> (usize, usize) {
+        let mut size = WinSize::default();
+        let res = unsafe { ioctl(self.tty.as_raw_fd(), TIOCGWINSZ, &mut size as *mut WinSize) };
+        if res == -1 || size.rows == 0 || size.cols == 0 {
+            return (80, 24);
+        }
+
+        (size.cols as usize, size.rows as usize)
+    }
+
+    fn cell(&mut self, x: usize, y: usize) -> Option<&mut Cell> {
+        if x >= self.width || y >= self.height {
+            return None;
+        }
+
+        self.cells.get_mut(y * self.width + x)
+    }
+}
+
+impl Display for Tty {
+    fn poll(&mut self) -> Option<Event> {
+        if RESIZED.swap(false, Ordering::SeqCst) {
+            let (width, height) = self.size();
+            self.width = width;
+            self.height = height;
+            self.cells = vec![Cell::blank(); width * height];
+            self.shown.clear();
+
+            return Some(Event::Redraw(width, height));
+        }
+
+        let mut bytes = [0; 256];
+        match self.tty.read(&mut bytes) {
+            Ok(0) if self.pending.is_empty() => None,
+            // a read that timed out ends a cut off sequence
+            Ok(len) => {
+                self.pending.extend_from_slice(&bytes[..len]);
+                let (keys, used) = parse_keys(&self.pending, len == 0);
+                self.pending.drain(..used);
+                if keys.is_empty() {
+                    None
+                } else {
+                    Some(Event::Keys(keys))
+                }
+            },
+            Err(ref err) if err.kind() == io::ErrorKind::Interrupted => None,
+            // the terminal went away
+            Err(_) => Some(Event::Close)
+        }
+    }
+
+    fn set_fg(&mut self, color: usize) {
+        self.fg = Color::Palette(color);
+    }
+
+    fn set_bg(&mut self, color: usize) {
+        self.bg = Color::Palette(color);
+    }
+
+    fn set_fg_rgb(&mut self, r: u8, g: u8, b: u8) {
+        self.fg = Color::Rgb(r, g, b);
+    }
+
+    fn set_bg_rgb(&mut self, r: u8, g: u8, b: u8) {
+        self.bg = Color::Rgb(r, g, b);
+    }
+
+    fn set_font(&mut self, font: usize) {
+        self.font = font;
+    }
+
+    fn set_decoration(&mut self, underline: bool, strikethrough: bool) {
+        self.underline = underline;
+        self.strikethrough = strikethrough;
+    }
+
+    // control chars would move the terminal cursor
+    // a wide char hides the char put right of it
+    fn put_char(&mut self, x: usize, y: usize, chr: char) {
+        if self.cell(x, y).is_some_and(|x| x.text.is_empty()) {
+            return;
+        }
+        // a wide char that was here leaves its right half
+        if self.cell(x + 1, y).is_some_and(|x| x.text.is_empty()) {
+            self.cell(x + 1, y).unwrap().text = String::from(" ");
+        }
+
+        let is_wide = wide(chr);
+        let text = if chr.is_control() || (is_wide && x + 1 >= self.width) {
+            String::from("?")
+        } else {
+            chr.to_string()
+        };
+        let cell = Cell {
+            text,
+            fg: self.fg,
+            bg: self.bg,
+            font: self.font,
+            underline: self.underline,
+            strikethrough: self.strikethrough
+        };
+        if is_wide {
+            if let Some(right) = self.cell(x + 1, y) {
+                *right = Cell {
+                    text: String::new(),
+                    ..cell.clone()
+                };
+            }
+        }
+        if let Some(left) = self.cell(x, y) {
+            *left = cell;
+        }
+    }
+
+    // combining chars go into the cell before them
+    fn put_str(&mut self, x: usize, y: usize, string: &str) {
+        let mut cell_x = x;
+        let mut last_x = None;
+        for chr in string.chars() {
+            if ('\u{300}'..='\u{36f}').contains(&chr) {
+                if let Some(cell) = last_x.and_then(|x| self.cell(x, y)) {
+                    cell.text.push(chr);
+                }
+                continue;
+            }
+
+            self.put_char(cell_x, y, chr);
+            last_x = Some(cell_x);
+            cell_x += if wide(chr) { 2 } else { 1 };
+        }
+    }
+
+    // the terminal cursor goes there on flush
+    fn put_cursor(&mut self, x: usize, y: usize) {
+        self.cursor = Some((x, y));
+    }
+
+    // writes the cells that changed since the last flush
+    fn flush(&mut self) {
+        let mut out = String::new();
+        let redraw = self.shown.len() != self.cells.len();
+        let mut style_maybe: Option<String> = None;
+        let mut next = None;
+
+        for (i, cell) in self.cells.iter().enumerate() {
+            if !redraw && self.shown[i] == *cell {
+                continue;
+            }
+
+            // the wide char left of it covers it
+            if cell.text.is_empty() {
+                continue;
+            }
+
+            let (x, y) = (i % self.width, i / self.width);
+            if next != Some(i) {
+                out.push_str(format!("\x1b[{};{}H", y + 1, x + 1).as_str());
+            }
+            let style = style(cell);
+            if style_maybe.as_ref() != Some(&style) {
+                out.push_str(style.as_str());
+                style_maybe = Some(style);
+            }
+            out.push_str(cell.text.as_str());
+            next = Some(if self.cells.get(i + 1).is_some_and(|x| x.text.is_empty()) {
+                i + 2
+            } else {
+                i + 1
+            });
+        }
+
+        match self.cursor.take() {
+            Some((x, y)) => out.push_str(format!("\x1b[{};{}H\x1b[?25h", y + 1, x + 1).as_str()),
+            None => out.push_str("\x1b[?25l")
+        }
+        self.shown = self.cells.clone();
+
+        let _ = self.tty.write_all(out.as_bytes()).and_then(|_| self.tty.flush());
+    }
+}
+
+impl Drop for Tty {
+    fn drop(&mut self) {
+        let _ = self.tty.write_all(b"\x1b[0m\x1b[?25h\x1b[?1049l");
+        let _ = stty(&self.tty, &[self.saved.as_str()]);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keys() {
+        let bytes = b"a\x1b[A\x1bOD\x1b[1;5B\x7f\r\x01";
+        let (keys, used) = parse_keys(bytes, false);
+        assert_eq!(keys, vec![
+            Key::Char('a'), Key::Up, Key::Left, Key::CtrlDown, Key::Backspace, Key::Enter, Key::Ctrl('a')
+        ]);
+        assert_eq!(used, bytes.len());
+    }
+
+    #[test]
+    fn unknown_sequences_are_dropped() {
+        let (keys, used) = parse_keys(b"\x1b[200~x\x1b[15;2~", false);
+        assert_eq!(keys, vec![Key::Char('x')]);
+        assert_eq!(used, 14);
+    }
+
+    #[test]
+    fn cut_sequences_wait() {
+        let (keys, used) = parse_keys(b"a\x1b[1;", false);
+        assert_eq!((keys, used), (vec![Key::Char('a')], 1));
+        let (keys, used) = parse_keys(b"\x1b[1;5A", false);
+        assert_eq!((keys, used), (vec![Key::CtrlUp], 6));
+
+        // a char split between reads
+        let bytes = "\u{e9}".as_bytes();
+        assert_eq!(parse_keys(&bytes[..1], false), (vec![], 0));
+        assert_eq!(parse_keys(bytes, false), (vec![Key::Char('\u{e9}')], 2));
+    }
+
+    #[test]
+    fn lone_esc_once_reads_stop() {
+        assert_eq!(parse_keys(b"\x1b", false), (vec![], 0));
+        assert_eq!(parse_keys(b"\x1b", true), (vec![Key::Esc], 1));
+        // alt and a key
+        assert_eq!(parse_keys(b"\x1bx", false), (vec![Key::Esc, Key::Char('x')], 2));
+    }
+
+    #[test]
+    fn invalid_bytes_are_dropped() {
+        assert_eq!(parse_keys(b"\xffa", false), (vec![Key::Char('a')], 2));
+        assert_eq!(parse_keys(b"\xc3", true), (vec![], 1));
+    }
+
+    // a tty of width by height cells writing nowhere
+    fn tty(width: usize, height: usize) -> Tty {
+        Tty {
+            tty: OpenOptions::new().write(true).open("/dev/null").unwrap(),
+            saved: String::new(),
+            pending: Vec::new(),
+
+            fg: Color::Palette(theme::FOREGROUND),
+            bg: Color::Palette(theme::BACKGROUND),
+            font: theme::FONT_REGULAR,
+            underline: false,
+            strikethrough: false,
+
+            width,
+            height,
+            cells: vec![Cell::blank(); width * height],
+            shown: Vec::new(),
+            cursor: None
+        }
+    }
+
+    fn texts(tty: &Tty) -> Vec<&str> {
+        tty.cells.iter().map(|x| x.text.as_str()).collect()
+    }
+
+    #[test]
+    fn control_chars_are_replaced() {
+        let mut tty = tty(3, 1);
+        tty.put_str(0, 0, "\x1b\x07a");
+        assert_eq!(texts(&tty), vec!["?", "?", "a"]);
+    }
+
+    #[test]
+    fn wide_chars_take_two_cells() {
+        let mut tty = tty(5, 1);
+        tty.put_str(0, 0, "\u{4e2d}e\u{301}\u{1f600}");
+        assert_eq!(texts(&tty), vec!["\u{4e2d}", "", "e\u{301}", "\u{1f600}", ""]);
+
+        // the view puts a char in every cell
+        tty.put_char(0, 0, '\u{6587}');
+        tty.put_char(1, 0, 'a');
+        assert_eq!(texts(&tty)[..2], ["\u{6587}", ""]);
+        tty.put_char(0, 0, 'b');
+        assert_eq!(texts(&tty)[..2], ["b", " "]);
+        // no room for the right half
+        tty.put_char(3, 0, 'c');
+        tty.put_char(4, 0, '\u{4e2d}');
+        assert_eq!(texts(&tty)[3..], ["c", "?"]);
+    }
+
+    #[test]
+    fn rgb_colors_are_24_bit() {
+        let mut cell = Cell::blank();
+        cell.fg = Color::Rgb(1, 2, 3);
+        cell.bg = Color::Palette(theme::FOREGROUND);
+        cell.font = theme::FONT_BOLD;
+        assert_eq!(style(&cell), "\x1b[0;1;38;2;1;2;3;47m");
+    }
+}
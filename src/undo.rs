@@ -0,0 +1,233 @@
+use std::cmp;
+
+use crate::buffer::Edit;
+use crate::view::Pos;
+use crate::JEdit;
+
+// undo by the edits the buffers log
+// grouped into one step per key
+
+// steps kept for each buffer
+const LIMIT: usize = 100;
+// older steps are dropped past this many bytes
+const BYTES_LIMIT: usize = 32 << 20;
+
+struct Step {
+    // in the order they were made
+    edits: Vec<Edit>,
+    // the cursor before and after them
+    before: Pos,
+    after: Pos
+}
+
+impl Step {
+    fn size(&self) -> usize {
+        self.edits.iter().map(|x| x.size()).sum()
+    }
+}
+
+pub struct History {
+    undo: Vec<Step>,
+    redo: Vec<Step>,
+    // where the last step left the cursor
+    cursor: Pos,
+    // the last step came from typing a word char
+    // so more typing joins it
+    typing: bool
+}
+
+impl History {
+    pub fn new() -> History {
+        History {
+            undo: Vec::new(),
+            redo: Vec::new(),
+            cursor: (0, 0),
+            typing: false
+        }
+    }
+
+    fn push(&mut self, edits: Vec<Edit>, cursor: Pos, typing: bool) {
+        let before = self.cursor;
+        self.cursor = cursor;
+        if edits.is_empty() {
+            return;
+        }
+
+        match self.undo.last_mut() {
+            Some(last) if typing && self.typing => {
+                last.edits.extend(edits);
+                last.after = cursor;
+            },
+            _ => self.undo.push(Step {
+                edits,
+                before,
+                after: cursor
+            })
+        }
+        self.typing = typing;
+        self.redo.clear();
+
+        let mut bytes: usize = self.undo.iter().map(|x| x.size()).sum();
+        while self.undo.len() > LIMIT || (bytes > BYTES_LIMIT && !self.undo.is_empty()) {
+            bytes -= self.undo.remove(0).size();
+        }
+    }
+}
+
+impl JEdit {
+    // makes steps of the edits since the last call
+    // and notes the cursor for the next one
+    // typed is set after a word char went into the focused buffer
+    pub(crate) fn record_edits(&mut self, typed: bool) {
+        let current = self.current();
+        for i in 0..self.buffers.len() {
+            let edits = self.buffers[i].take_edits();
+            // where a view last had the cursor
+            let cursor = match self.views.iter().find(|x| x.buffer == i) {
+                Some(view) => view.cursor,
+                None => self.places[i].cursor
+            };
+            self.places[i].history.push(edits, (cursor.x, cursor.y), typed && i == current);
+        }
+    }
+
+    pub(crate) fn undo(&mut self) {
+        self.step(true);
+    }
+
+    pub(crate) fn redo(&mut self) {
+        self.step(false);
+    }
+
+    fn step(&mut self, back: bool) {
+        let current = self.current();
+        if !self.buffers[current].check_edit() {
+            return;
+        }
+        self.record_edits(false);
+
+        let history = &mut self.places[current].history;
+        let step_maybe = if back {
+            history.undo.pop()
+        } else {
+            history.redo.pop()
+        };
+        let step = match step_maybe {
+            Some(step) => step,
+            None => {
+                self.message = Some(String::from(if back { "nothing to undo" } else { "nothing to redo" }));
+                return;
+            }
+        };
+
+        let buffer = &mut self.buffers[current];
+        let cursor = if back {
+            for edit in step.edits.iter().rev() {
+                buffer.apply(&edit.opposite());
+            }
+            step.before
+        } else {
+            for edit in step.edits.iter() {
+                buffer.apply(edit);
+            }
+            step.after
+        };
+        // the step is already in the history
+        buffer.take_edits();
+
+        let history = &mut self.places[current].history;
+        history.cursor = cursor;
+        history.typing = false;
+        if back {
+            history.redo.push(step);
+        } else {
+            history.undo.push(step);
+        }
+        self.clamp_views(current);
+
+        let buffer = &self.buffers[current];
+        let y = cmp::min(cursor.1, buffer.height().saturating_sub(1));
+        let x = cmp::min(cursor.0, buffer.line_len(y));
+        self.move_cursor(x, y);
+        self.view_mut().cursor.fake_x = x;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::editor;
+
+    fn insert(x: usize, chr: char) -> Vec<Edit> {
+        vec![Edit::Insert(x, 0, chr)]
+    }
+
+    #[test]
+    fn typing_joins_one_step() {
+        let mut history = History::new();
+        history.push(insert(0, 'a'), (1, 0), true);
+        history.push(insert(1, 'b'), (2, 0), true);
+        history.push(insert(2, ' '), (3, 0), false);
+        history.push(insert(3, 'c'), (4, 0), true);
+        let lens: Vec<usize> = history.undo.iter().map(|x| x.edits.len()).collect();
+        assert_eq!(lens, vec![2, 1, 1]);
+        assert_eq!((history.undo[1].before, history.undo[1].after), ((2, 0), (3, 0)));
+    }
+
+    #[test]
+    fn edits_clear_redo() {
+        let mut history = History::new();
+        history.redo.push(Step {
+            edits: insert(0, 'x'),
+            before: (0, 0),
+            after: (1, 0)
+        });
+        // moving the cursor is no step
+        history.push(Vec::new(), (0, 0), false);
+        assert_eq!(history.redo.len(), 1);
+        history.push(insert(0, 'y'), (1, 0), false);
+        assert!(history.redo.is_empty());
+    }
+
+    #[test]
+    fn old_steps_are_dropped() {
+        let mut history = History::new();
+        for i in 0..LIMIT + 10 {
+            history.push(insert(i, 'a'), (i + 1, 0), false);
+        }
+        assert_eq!(history.undo.len(), LIMIT);
+        assert_eq!(history.undo[0].edits, insert(10, 'a'));
+    }
+
+    #[test]
+    fn undo_and_redo_edits() {
+        let mut jedit = editor("ab\ncd");
+        jedit.move_cursor(1, 0);
+        jedit.record_edits(false);
+        jedit.insert_raw("x\ny");
+        jedit.record_edits(false);
+        jedit.delete_range((0, 1), (1, 2));
+        jedit.record_edits(false);
+        assert_eq!(jedit.buffer().text(), "ax\nd");
+
+        jedit.undo();
+        assert_eq!(jedit.buffer().text(), "ax\nyb\ncd");
+        jedit.undo();
+        assert_eq!(jedit.buffer().text(), "ab\ncd");
+        assert_eq!((jedit.view().cursor.x, jedit.view().cursor.y), (1, 0));
+        jedit.redo();
+        jedit.redo();
+        assert_eq!(jedit.buffer().text(), "ax\nd");
+    }
+
+    #[test]
+    fn whole_text_replacements_are_one_step() {
+        let mut jedit = editor("a");
+        jedit.replace_text("b\nc");
+        jedit.record_edits(false);
+        jedit.undo();
+        assert_eq!(jedit.buffer().text(), "a");
+        jedit.redo();
+        assert_eq!(jedit.buffer().text(), "b\nc");
+    }
+}
@@ -0,0 +1,583 @@
+use std::cmp;
+use std::mem;
+use std::ops::Range;
+
+use crate::display::Display;
+
+use crate::buffer::Buffer;
+use crate::theme::{self, Style, Styles};
+
+// right aligns string in n columns
+fn pad(mut string: String, n: usize) -> String {
+    let len = string.chars().count();
+    if len < n {
+        for _ in len..n {
+            string.insert(0, ' ');
+        }
+    }
+
+    string
+}
+
+// (x, y) in buffer coordinates
+pub type Pos = (usize, usize);
+
+// start and end of a range by line first
+pub fn ordered(a: Pos, b: Pos) -> (Pos, Pos) {
+    if (a.1, a.0) <= (b.1, b.0) {
+        (a, b)
+    } else {
+        (b, a)
+    }
+}
+
+// new start of a window of size over a line
+// so pos is margin away from both edges
+// a margin too big for the window centers pos
+fn scroll(start: usize, pos: usize, size: usize, margin: usize) -> usize {
+    if size == 0 {
+        return start;
+    }
+
+    let margin = cmp::min(margin, (size - 1) / 2);
+    if pos < start + margin {
+        pos.saturating_sub(margin)
+    } else if pos + margin >= start + size {
+        pos + margin + 1 - size
+    } else {
+        start
+    }
+}
+
+// where align puts the cursor line
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Align {
+    Top,
+    Center,
+    Bottom
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Wrap {
+    Off,
+    // at the view width
+    Char,
+    // after the last space that fits
+    Word
+}
+
+// cursor struct to handle
+// cursor specific things
+#[derive(Copy, Clone, Debug)]
+pub struct Cursor {
+    pub x: usize, pub y: usize,
+    pub fake_x: usize
+}
+
+impl Cursor {
+    pub fn new() -> Cursor {
+        Cursor {
+            x: 0, y: 0,
+            fake_x: 0
+        }
+    }
+}
+
+// a window onto a buffer
+// several views can show the same buffer
+pub struct View {
+    // index into JEdit::buffers
+    pub buffer: usize,
+
+    // screen position
+    pub x: usize, pub y: usize,
+    pub width: usize, pub height: usize,
+    pub buffer_width: usize, pub buffer_height: usize,
+
+    pub cursor: Cursor,
+
+    pub view_x: usize,
+    pub view_y: usize,
+
+    // line number offset
+    pub offset_x: usize,
+    pub line_numbers: bool,
+    pub wrap: Wrap,
+
+    // chars and lines between the cursor and the edges
+    // None keeps the cursor in the middle half
+    pub margin_x: Option<usize>,
+    pub margin_y: Option<usize>,
+    // false after the view is scrolled away from the cursor
+    follow: bool,
+
+    // highlighted range, end exclusive
+    pub selection: Option<(Pos, Pos)>
+}
+
+impl View {
+    pub fn new(buffer: usize) -> View {
+        View {
+            buffer,
+
+            x: 0, y: 0,
+            width: 0, height: 0,
+            buffer_width: 0, buffer_height: 0,
+
+            cursor: Cursor::new(),
+
+            view_x: 0,
+            view_y: 0,
+
+            offset_x: 0,
+            line_numbers: true,
+            wrap: Wrap::Off,
+
+            margin_x: None,
+            margin_y: None,
+            follow: true,
+
+            selection: None
+        }
+    }
+
+    fn selected(&self, x: usize, y: usize) -> bool {
+        match self.selection {
+            Some((start, end)) => (y, x) >= (start.1, start.0) && (y, x) < (end.1, end.0),
+            None => false
+        }
+    }
+
+    pub fn move_cursor(&mut self, x: usize, y: usize) {
+        self.cursor.x = x;
+        self.cursor.y = y;
+        self.follow = true;
+        self.scroll_to_cursor();
+    }
+
+    fn scroll_to_cursor(&mut self) {
+        // without a margin the cursor stays in the middle half
+        let margin_x = self.margin_x.unwrap_or(self.buffer_width / 4);
+        let margin_y = self.margin_y.unwrap_or(self.buffer_height / 4);
+        self.view_x = scroll(self.view_x, self.cursor.x, self.buffer_width, margin_x);
+        self.view_y = scroll(self.view_y, self.cursor.y, self.buffer_height, margin_y);
+    }
+
+    // puts the cursor line at the top, middle or bottom
+    pub fn align(&mut self, align: Align) {
+        let above = match align {
+            Align::Top => 0,
+            Align::Center => self.buffer_height / 2,
+            Align::Bottom => self.buffer_height.saturating_sub(1)
+        };
+        self.view_y = self.cursor.y.saturating_sub(above);
+        self.follow = false;
+    }
+
+    // moves the view but not the cursor
+    pub fn scroll_by(&mut self, lines: isize, buffer_height: usize) {
+        let view_y = self.view_y as isize + lines;
+        self.view_y = cmp::max(cmp::min(view_y, buffer_height as isize - 1), 0) as usize;
+        self.follow = false;
+    }
+
+    // must call after you clear the line
+    // draws the chars of line y in range on screen row
+    fn draw_line(&self, win: &mut dyn Display, buffer: &Buffer, row: usize, y: usize, range: Range<usize>, styles: &Styles) {
+        // the cursor line gets its own background
+        let line_bg = if y == self.cursor.y {
+            theme::CURSOR_LINE
+        } else {
+            theme::BACKGROUND
+        };
+
+        let line_maybe = buffer.line(y);
+        if line_maybe.is_none() {
+            win.set_bg(theme::BACKGROUND);
+            for x in 0..self.buffer_width {
+                win.put_char(x + self.x + self.offset_x, row + self.y, ' ');
+            }
+            return;
+        }
+
+        let line = line_maybe.as_ref().unwrap();
+        for x in 0..self.buffer_width {
+            let chr_maybe = if range.contains(&(x + range.start)) {
+                line[x + range.start].as_ref()
+            } else {
+                None
+            };
+            if chr_maybe.is_none() {
+                win.set_bg(line_bg);
+                win.set_decoration(false, false);
+                win.put_char(x + self.x + self.offset_x, row + self.y, ' ');
+                continue;
+            }
+
+            let chr = chr_maybe.unwrap();
+            if self.selected(x + range.start, y) {
+                win.set_bg(theme::SELECTION);
+            } else if chr.bg == theme::BACKGROUND {
+                win.set_bg(line_bg);
+            } else {
+                win.set_bg(chr.bg);
+            }
+            win.set_fg(chr.fg);
+
+            // fonts set on the char win over the theme
+            let style = match chr.fg {
+                0..=23 => styles[chr.fg % 8],
+                _ => Style::default()
+            };
+            win.set_font(if chr.font != theme::FONT_REGULAR {
+                chr.font
+            } else {
+                style.font()
+            });
+
+            let shown = if chr.chr == '\t' {
+                ' '
+            } else {
+                chr.chr
+            };
+            style.decorate(win);
+            win.put_char(x + self.x + self.offset_x, row + self.y, shown);
+        }
+        win.set_decoration(false, false);
+    }
+
+    // line number, wrap indicator or nothing
+    fn draw_gutter(&self, win: &mut dyn Display, row: usize, label: &str) {
+        win.set_bg(theme::BACKGROUND);
+        win.set_fg(theme::GUTTER);
+        win.set_font(theme::FONT_BOLD);
+
+        let mut label = pad(format!("{} ", label), self.offset_x);
+        // narrow splits cut the gutter
+        label = label.chars().take(cmp::min(self.offset_x, self.width)).collect();
+        win.put_str(self.x, row + self.y, label.as_str());
+        win.set_font(theme::FONT_REGULAR);
+    }
+
+    // screen rows of line y as char ranges
+    // there is always at least one
+    pub fn segments(&self, buffer: &Buffer, y: usize) -> Vec<Range<usize>> {
+        let len = buffer.line_len(y);
+        let width = self.buffer_width;
+        let is_space = |x: usize| buffer.get(x, y).is_some_and(|x| x.chr == ' ' || x.chr == '\t');
+        let mut segments = Vec::new();
+        let mut start = 0;
+        while self.wrap != Wrap::Off && width != 0 && len - start > width {
+            let mut end = start + width;
+            // break after the last space that fits
+            if self.wrap == Wrap::Word {
+                if let Some(x) = ((start + 1)..=end).rev().find(|x| is_space(x - 1)) {
+                    end = x;
+                }
+            }
+
+            segments.push(start..end);
+            start = end;
+        }
+        segments.push(start..len);
+
+        segments
+    }
+
+    // segment of line y that shows x
+    // x at the end of a full row goes on the next one
+    pub fn segment_of(segments: &[Range<usize>], x: usize) -> usize {
+        segments.iter()
+            .position(|range| range.contains(&x))
+            .unwrap_or(segments.len() - 1)
+    }
+
+    // scrolls by whole lines until the row of the cursor
+    // fits, rows don't have to match lines
+    fn scroll_wrapped(&mut self, buffer: &Buffer) {
+        self.view_x = 0;
+        if self.cursor.y < self.view_y {
+            self.view_y = self.cursor.y;
+        }
+
+        loop {
+            let mut rows = 0;
+            for y in self.view_y..self.cursor.y {
+                rows += self.segments(buffer, y).len();
+            }
+            let segments = self.segments(buffer, self.cursor.y);
+            rows += View::segment_of(&segments, self.cursor.x) + 1;
+
+            if rows <= self.buffer_height || self.view_y >= self.cursor.y {
+                break;
+            }
+            self.view_y += 1;
+        }
+    }
+
+    pub fn draw(&mut self, win: &mut dyn Display, buffer: &Buffer, focused: bool, styles: &Styles) {
+        self.buffer_height = self.height;
+
+        let end = cmp::min(self.buffer_height + self.view_y, buffer.height());
+
+        self.offset_x = if !self.line_numbers {
+            0
+        } else if end == 0 {
+            1
+        } else {
+            format!("{}", end).len() + 1 // good logarithm
+        };
+        if self.width >= self.offset_x {
+            self.buffer_width = self.width - self.offset_x;
+        } else {
+            self.buffer_width = 0;
+        }
+        // the cursor may have moved before the size was known
+        if self.follow {
+            if self.wrap == Wrap::Off {
+                self.scroll_to_cursor();
+            } else {
+                self.scroll_wrapped(buffer);
+            }
+        }
+
+        // lines take one row each unless they wrap
+        let mut cursor_at = None;
+        let mut row = 0;
+        let mut y = self.view_y;
+        while row < self.buffer_height {
+            if y >= buffer.height() {
+                self.draw_gutter(win, row, "");
+                self.draw_line(win, buffer, row, y, 0..0, styles);
+                row += 1;
+                continue;
+            }
+
+            let mut segments = self.segments(buffer, y);
+            if self.wrap == Wrap::Off {
+                segments[0] = self.view_x..(self.view_x + self.buffer_width);
+            }
+            let cursor_segment = View::segment_of(&segments, self.cursor.x);
+            for (i, range) in segments.into_iter().enumerate() {
+                if row >= self.buffer_height {
+                    break;
+                }
+
+                let label = match (i, self.line_numbers) {
+                    (0, true) => format!("{}", y + 1),
+                    (_, true) => String::from("\u{21aa}"),
+                    _ => String::new()
+                };
+                self.draw_gutter(win, row, label.as_str());
+                if y == self.cursor.y && i == cursor_segment && self.cursor.x >= range.start {
+                    cursor_at = Some((self.cursor.x - range.start, row));
+                }
+                self.draw_line(win, buffer, row, y, range, styles);
+                row += 1;
+            }
+            y += 1;
+        }
+
+        // the cursor is only drawn if it is inside the view
+        if let Some((x, row)) = cursor_at {
+            if focused && x < self.buffer_width {
+                win.set_bg(theme::CURSOR_LINE);
+                win.set_fg(theme::FOREGROUND);
+                win.set_font(theme::FONT_BOLD);
+                win.put_cursor(x + self.offset_x + self.x, row + self.y);
+            }
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Dir {
+    // stacked top to bottom
+    Horizontal,
+    // side by side
+    Vertical
+}
+
+// tiling of the window
+// leaves are indices into JEdit::views
+pub enum Layout {
+    View(usize),
+    Split(Dir, Box<Layout>, Box<Layout>)
+}
+
+impl Layout {
+    // splits the leaf of view into view and new_view
+    pub fn split(&mut self, view: usize, new_view: usize, dir: Dir) -> bool {
+        match self {
+            Layout::View(i) if *i == view => {
+                *self = Layout::Split(dir, Box::new(Layout::View(view)), Box::new(Layout::View(new_view)));
+                true
+            },
+            Layout::View(_) => false,
+            Layout::Split(_, a, b) => a.split(view, new_view, dir) || b.split(view, new_view, dir)
+        }
+    }
+
+    // removes the leaf of view and shifts
+    // the indices of the views after it
+    pub fn remove(&mut self, view: usize) {
+        self.unlink(view);
+        self.shift(view);
+    }
+
+    // the sibling of the leaf takes the place of their split
+    fn unlink(&mut self, view: usize) {
+        if let Layout::Split(_, a, b) = self {
+            let sibling = if a.is(view) {
+                Some(mem::replace(&mut **b, Layout::View(0)))
+            } else if b.is(view) {
+                Some(mem::replace(&mut **a, Layout::View(0)))
+            } else {
+                None
+            };
+
+            match sibling {
+                Some(sibling) => *self = sibling,
+                None => {
+                    a.unlink(view);
+                    b.unlink(view);
+                }
+            }
+        }
+    }
+
+    fn is(&self, view: usize) -> bool {
+        match self {
+            Layout::View(i) => *i == view,
+            _ => false
+        }
+    }
+
+    fn shift(&mut self, view: usize) {
+        match self {
+            Layout::View(i) => if *i > view {
+                *i -= 1;
+            },
+            Layout::Split(_, a, b) => {
+                a.shift(view);
+                b.shift(view);
+            }
+        }
+    }
+
+    // view indices in screen order
+    pub fn order(&self, out: &mut Vec<usize>) {
+        match self {
+            Layout::View(i) => out.push(*i),
+            Layout::Split(_, a, b) => {
+                a.order(out);
+                b.order(out);
+            }
+        }
+    }
+
+    // gives every view its rectangle and
+    // draws the separators between them
+    pub fn place(&self, views: &mut Vec<View>, win: &mut dyn Display, x: usize, y: usize, width: usize, height: usize) {
+        match self {
+            Layout::View(i) => {
+                let view = &mut views[*i];
+                view.x = x;
+                view.y = y;
+                view.width = width;
+                view.height = height;
+            },
+            Layout::Split(Dir::Horizontal, a, b) => {
+                let top = height / 2;
+                a.place(views, win, x, y, width, top);
+
+                win.set_bg(theme::BACKGROUND);
+                win.set_fg(theme::FOREGROUND);
+                win.set_font(theme::FONT_REGULAR);
+                if height > top {
+                    win.put_str(x, y + top, "-".repeat(width).as_str());
+                }
+
+                b.place(views, win, x, y + top + 1, width, height.saturating_sub(top + 1));
+            },
+            Layout::Split(Dir::Vertical, a, b) => {
+                let left = width / 2;
+                a.place(views, win, x, y, left, height);
+
+                win.set_bg(theme::BACKGROUND);
+                win.set_fg(theme::FOREGROUND);
+                win.set_font(theme::FONT_REGULAR);
+                if width > left {
+                    for i in 0..height {
+                        win.put_char(x + left, y + i, '|');
+                    }
+                }
+
+                b.place(views, win, x + left + 1, y, width.saturating_sub(left + 1), height);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::display::Headless;
+
+    fn view(wrap: Wrap, width: usize) -> View {
+        let mut view = View::new(0);
+        view.wrap = wrap;
+        view.buffer_width = width;
+        view
+    }
+
+    fn buffer(text: &str) -> Buffer {
+        let mut buffer = Buffer::new(None);
+        buffer.set_text(text);
+        buffer
+    }
+
+    #[test]
+    fn pad_counts_chars() {
+        assert_eq!(pad(String::from("\u{21aa} "), 4), "  \u{21aa} ");
+        assert_eq!(pad(String::from("12 "), 2), "12 ");
+    }
+
+    #[test]
+    fn segments() {
+        let buffer = buffer("abcdefghij\nab cd ef\n");
+        assert_eq!(view(Wrap::Off, 4).segments(&buffer, 0), vec![0..10]);
+        assert_eq!(view(Wrap::Char, 4).segments(&buffer, 0), vec![0..4, 4..8, 8..10]);
+        assert_eq!(view(Wrap::Char, 5).segments(&buffer, 0), vec![0..5, 5..10]);
+        // after the last space that fits
+        assert_eq!(view(Wrap::Word, 5).segments(&buffer, 1), vec![0..3, 3..8]);
+        // a word longer than the row is cut
+        assert_eq!(view(Wrap::Word, 3).segments(&buffer, 0), vec![0..3, 3..6, 6..9, 9..10]);
+        assert_eq!(view(Wrap::Char, 4).segments(&buffer, 2), vec![0..0]);
+    }
+
+    #[test]
+    fn segment_of() {
+        let segments = vec![0..4, 4..8, 8..10];
+        assert_eq!(View::segment_of(&segments, 0), 0);
+        assert_eq!(View::segment_of(&segments, 3), 0);
+        // the end of a full row goes on the next one
+        assert_eq!(View::segment_of(&segments, 4), 1);
+        // past the last char
+        assert_eq!(View::segment_of(&segments, 10), 2);
+    }
+
+    #[test]
+    fn draw_scrolls_to_an_early_jump() {
+        let buffer = buffer(&"a\n".repeat(100));
+        let mut view = view(Wrap::Off, 0);
+        // before the first draw the view has no size
+        view.move_cursor(0, 50);
+        assert_eq!(view.view_y, 0);
+
+        view.width = 20;
+        view.height = 10;
+        view.draw(&mut Headless, &buffer, true, &[Style::default(); 8]);
+        assert!(view.view_y <= 50 && 50 < view.view_y + 10);
+    }
+}
@@ -0,0 +1,846 @@
+use std::cmp;
+use std::mem;
+use std::path::Path;
+
+use crate::command;
+use crate::key::Key;
+use crate::prompt::Ask;
+use crate::view::{self, Align, Dir, Pos};
+use crate::JEdit;
+
+// modal editing on top of the normal editing commands
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum VimMode {
+    Normal,
+    Insert,
+    Visual
+}
+
+// how a motion covers text when an operator uses it
+#[derive(Copy, Clone, PartialEq, Eq)]
+enum Span {
+    // up to the target
+    Exclusive,
+    // the target char is included
+    Inclusive,
+    // whole lines
+    Linewise
+}
+
+pub struct Vim {
+    pub mode: VimMode,
+
+    // 0 when no count is typed
+    count: usize,
+    // count typed before the operator
+    op_count: usize,
+    operator: Option<char>,
+    // after a single g or z
+    g: bool,
+    z: bool,
+
+    // other end of the visual selection
+    anchor: Pos,
+
+    register: String,
+    linewise: bool,
+
+    // keys of the command being typed
+    keys: Vec<Key>,
+    // keys of a change that is still in insert mode
+    recording: Option<Vec<Key>>,
+    // replayed by .
+    last_change: Vec<Key>
+}
+
+impl Vim {
+    pub fn new() -> Vim {
+        Vim {
+            mode: VimMode::Normal,
+
+            count: 0,
+            op_count: 0,
+            operator: None,
+            g: false,
+            z: false,
+
+            anchor: (0, 0),
+
+            register: String::new(),
+            linewise: false,
+
+            keys: Vec::new(),
+            recording: None,
+            last_change: Vec::new()
+        }
+    }
+
+    // forgets a half typed command
+    fn reset(&mut self) {
+        self.count = 0;
+        self.op_count = 0;
+        self.operator = None;
+        self.g = false;
+        self.z = false;
+        self.keys.clear();
+    }
+
+    // counts before and after the operator multiply
+    fn take_count(&mut self) -> usize {
+        let count = cmp::max(self.count, 1) * cmp::max(self.op_count, 1);
+        self.count = 0;
+        self.op_count = 0;
+        count
+    }
+
+    // Some while insert mode is part of a change
+    pub fn record(&mut self, key: Key) {
+        if let Some(recording) = self.recording.as_mut() {
+            recording.push(key);
+        }
+    }
+}
+
+// whitespace, word or punctuation
+fn class(chr: char) -> usize {
+    if chr.is_whitespace() {
+        0
+    } else if chr.is_alphanumeric() || chr == '_' {
+        1
+    } else {
+        2
+    }
+}
+
+impl JEdit {
+    pub(crate) fn vim_toggle(&mut self) {
+        if self.vim.is_some() {
+            self.vim = None;
+            self.view_mut().selection = None;
+            self.message = Some(String::from("vim off"));
+        } else {
+            self.vim = Some(Vim::new());
+            self.message = Some(String::from("vim on"));
+        }
+    }
+
+    // back to normal mode from insert or visual mode
+    pub(crate) fn vim_normal(&mut self) {
+        let vim = match self.vim.as_mut() {
+            Some(vim) => vim,
+            None => return
+        };
+
+        if vim.mode == VimMode::Insert {
+            // the esc ends the recorded change
+            if let Some(recording) = vim.recording.take() {
+                vim.last_change = recording;
+            }
+
+            // the cursor goes back onto the last typed char
+            let cursor = self.view().cursor;
+            if cursor.x != 0 {
+                self.move_cursor(cursor.x - 1, cursor.y);
+                self.view_mut().cursor.fake_x = cursor.x - 1;
+            }
+        }
+
+        let vim = self.vim.as_mut().unwrap();
+        vim.mode = VimMode::Normal;
+        vim.reset();
+        self.view_mut().selection = None;
+    }
+
+    // shown in the status bar
+    pub(crate) fn vim_label(&self) -> Option<&'static str> {
+        match self.vim.as_ref()?.mode {
+            VimMode::Normal => None,
+            VimMode::Insert => Some("-- INSERT --"),
+            VimMode::Visual => Some("-- VISUAL --")
+        }
+    }
+
+    // char at x or a newline past the end of a line
+    // None past the end of the buffer
+    fn char_at(&self, p: Pos) -> Option<char> {
+        let buffer = self.buffer();
+        if p.1 >= buffer.height() {
+            return None;
+        }
+
+        match buffer.get(p.0, p.1) {
+            Some(chr) => Some(chr.chr),
+            None if p.1 + 1 < buffer.height() => Some('\n'),
+            None => None
+        }
+    }
+
+    fn next_pos(&self, p: Pos) -> Option<Pos> {
+        if p.0 < self.buffer().line_len(p.1) {
+            Some((p.0 + 1, p.1))
+        } else if p.1 + 1 < self.buffer().height() {
+            Some((0, p.1 + 1))
+        } else {
+            None
+        }
+    }
+
+    fn prev_pos(&self, p: Pos) -> Option<Pos> {
+        if p.0 != 0 {
+            Some((p.0 - 1, p.1))
+        } else if p.1 != 0 {
+            Some((self.buffer().line_len(p.1 - 1), p.1 - 1))
+        } else {
+            None
+        }
+    }
+
+    fn class_at(&self, p: Pos) -> usize {
+        self.char_at(p).map_or(0, class)
+    }
+
+    fn first_non_blank(&self, y: usize) -> usize {
+        let len = self.buffer().line_len(y);
+        (0..len).find(|x| self.class_at((*x, y)) != 0).unwrap_or(len)
+    }
+
+    // start of the next word
+    fn word_forward(&self, mut p: Pos) -> Pos {
+        let start = self.class_at(p);
+        while let Some(next) = self.next_pos(p) {
+            p = next;
+            if self.class_at(p) != start {
+                break;
+            }
+        }
+        while self.class_at(p) == 0 {
+            match self.next_pos(p) {
+                Some(next) => p = next,
+                None => break
+            }
+        }
+
+        p
+    }
+
+    // last char of this or the next word
+    fn word_end(&self, mut p: Pos) -> Pos {
+        if let Some(next) = self.next_pos(p) {
+            p = next;
+        }
+        while self.class_at(p) == 0 {
+            match self.next_pos(p) {
+                Some(next) => p = next,
+                None => return p
+            }
+        }
+
+        let class = self.class_at(p);
+        while let Some(next) = self.next_pos(p) {
+            if self.class_at(next) != class {
+                break;
+            }
+            p = next;
+        }
+
+        p
+    }
+
+    // start of this or the previous word
+    fn word_back(&self, mut p: Pos) -> Pos {
+        if let Some(prev) = self.prev_pos(p) {
+            p = prev;
+        }
+        while self.class_at(p) == 0 {
+            match self.prev_pos(p) {
+                Some(prev) => p = prev,
+                None => return p
+            }
+        }
+
+        let class = self.class_at(p);
+        while let Some(prev) = self.prev_pos(p) {
+            if self.class_at(prev) != class {
+                break;
+            }
+            p = prev;
+        }
+
+        p
+    }
+
+    // where a motion goes from the cursor
+    fn motion(&self, chr: char, count: usize, counted: bool) -> Option<(Pos, Span)> {
+        let cursor = self.view().cursor;
+        let p = (cursor.x, cursor.y);
+        let last = self.buffer().height().saturating_sub(1);
+
+        let mut target = p;
+        let span = match chr {
+            'h' => {
+                target.0 = cursor.x.saturating_sub(count);
+                Span::Exclusive
+            },
+            'l' => {
+                target.0 = cmp::min(cursor.x + count, self.buffer().line_len(cursor.y));
+                Span::Exclusive
+            },
+            'j' | 'k' => {
+                target.1 = if chr == 'j' {
+                    cmp::min(cursor.y + count, last)
+                } else {
+                    cursor.y.saturating_sub(count)
+                };
+                target.0 = cmp::min(cursor.fake_x, self.buffer().line_len(target.1));
+                Span::Linewise
+            },
+            'w' => {
+                for _ in 0..count {
+                    target = self.word_forward(target);
+                }
+                Span::Exclusive
+            },
+            'e' => {
+                for _ in 0..count {
+                    target = self.word_end(target);
+                }
+                Span::Inclusive
+            },
+            'b' => {
+                for _ in 0..count {
+                    target = self.word_back(target);
+                }
+                Span::Exclusive
+            },
+            '0' => {
+                target.0 = 0;
+                Span::Exclusive
+            },
+            '^' => {
+                target.0 = self.first_non_blank(cursor.y);
+                Span::Exclusive
+            },
+            '$' => {
+                target.1 = cmp::min(cursor.y + count - 1, last);
+                target.0 = self.buffer().line_len(target.1).saturating_sub(1);
+                Span::Inclusive
+            },
+            // G and gg without a count go to the last and first line
+            'G' | 'g' => {
+                target.1 = if counted {
+                    cmp::min(count - 1, last)
+                } else if chr == 'G' {
+                    last
+                } else {
+                    0
+                };
+                target.0 = self.first_non_blank(target.1);
+                Span::Linewise
+            },
+            // next and previous empty line
+            '}' => {
+                for _ in 0..count {
+                    target.1 = ((target.1 + 1)..=last).find(|y| self.buffer().line_len(*y) == 0).unwrap_or(last);
+                }
+                target.0 = if target.1 == last {
+                    self.buffer().line_len(last)
+                } else {
+                    0
+                };
+                Span::Exclusive
+            },
+            '{' => {
+                for _ in 0..count {
+                    target.1 = (0..target.1).rev().find(|y| self.buffer().line_len(*y) == 0).unwrap_or(0);
+                }
+                target.0 = 0;
+                Span::Exclusive
+            },
+            _ => return None
+        };
+
+        Some((target, span))
+    }
+
+    fn move_to(&mut self, p: Pos) {
+        self.move_cursor(p.0, p.1);
+        self.view_mut().cursor.fake_x = p.0;
+    }
+
+    fn set_register(&mut self, text: String, linewise: bool) {
+        let vim = self.vim.as_mut().unwrap();
+        vim.register = text;
+        vim.linewise = linewise;
+    }
+
+    // linewise delete of lines y0 to y1
+    fn delete_lines(&mut self, y0: usize, y1: usize) {
+        let height = self.buffer().height();
+        let y1 = cmp::min(y1, height.saturating_sub(1));
+        let end = (self.buffer().line_len(y1), y1);
+
+        let mut text = self.text_range((0, y0), end);
+        text.push('\n');
+        self.set_register(text, true);
+
+        // the last lines take the newline before them
+        if y1 + 1 < height {
+            self.delete_range((0, y0), (0, y1 + 1));
+        } else if y0 != 0 {
+            self.delete_range((self.buffer().line_len(y0 - 1), y0 - 1), end);
+        } else {
+            self.delete_range((0, 0), end);
+        }
+
+        let y = cmp::min(y0, self.buffer().height().saturating_sub(1));
+        let x = self.first_non_blank(y);
+        self.move_to((x, y));
+    }
+
+    // leading tabs are stored tab_width chars wide
+    // so a range takes or leaves their groups whole
+    fn round_tabs(&self, mut start: Pos, mut end: Pos) -> (Pos, Pos) {
+        let tab_width = self.buffer().tab_width();
+        if start.0 < self.leading_tabs(start.1) {
+            start.0 -= start.0 % tab_width;
+        }
+        let leading = self.leading_tabs(end.1);
+        if end.0 < leading && !end.0.is_multiple_of(tab_width) {
+            end.0 = cmp::min(end.0 + tab_width - end.0 % tab_width, leading);
+        }
+
+        (start, end)
+    }
+
+    // runs operator on start to end
+    fn operate(&mut self, operator: char, start: Pos, end: Pos, span: Span) {
+        let (start, mut end) = view::ordered(start, end);
+        if span == Span::Linewise {
+            match operator {
+                'd' => self.delete_lines(start.1, end.1),
+                'c' => {
+                    let end = (self.buffer().line_len(end.1), end.1);
+                    let mut text = self.delete_range((0, start.1), end);
+                    text.push('\n');
+                    self.set_register(text, true);
+                    self.vim_insert();
+                },
+                _ => {
+                    let end = (self.buffer().line_len(end.1), end.1);
+                    let mut text = self.text_range((0, start.1), end);
+                    text.push('\n');
+                    self.set_register(text, true);
+                    self.move_to((self.view().cursor.x, start.1));
+                }
+            }
+            return;
+        }
+
+        if span == Span::Inclusive {
+            end.0 += 1;
+        }
+        let (start, end) = self.round_tabs(start, end);
+        match operator {
+            'd' | 'c' => {
+                let text = self.delete_range(start, end);
+                self.set_register(text, false);
+                if operator == 'c' {
+                    self.vim_insert();
+                }
+            },
+            _ => {
+                let text = self.text_range(start, end);
+                self.set_register(text, false);
+                self.move_to(start);
+            }
+        }
+    }
+
+    fn vim_insert(&mut self) {
+        let vim = self.vim.as_mut().unwrap();
+        vim.mode = VimMode::Insert;
+        // the keys so far start the change
+        if vim.recording.is_none() {
+            vim.recording = Some(mem::take(&mut vim.keys));
+        }
+    }
+
+    fn paste(&mut self, after: bool, count: usize) {
+        let vim = self.vim.as_ref().unwrap();
+        let (text, linewise) = (vim.register.repeat(count), vim.linewise);
+        if text.is_empty() {
+            return;
+        }
+
+        let cursor = self.view().cursor;
+        if linewise {
+            let body = text.strip_suffix('\n').unwrap_or(text.as_str());
+            let y = if after {
+                self.move_to((self.buffer().line_len(cursor.y), cursor.y));
+                self.insert_raw(format!("\n{}", body).as_str());
+                cursor.y + 1
+            } else {
+                self.move_to((0, cursor.y));
+                self.insert_raw(format!("{}\n", body).as_str());
+                cursor.y
+            };
+            let x = self.first_non_blank(y);
+            self.move_to((x, y));
+        } else {
+            if after {
+                let x = cmp::min(cursor.x + 1, self.buffer().line_len(cursor.y));
+                self.move_to((x, cursor.y));
+            }
+            self.insert_raw(text.as_str());
+
+            // on the last pasted char
+            let cursor = self.view().cursor;
+            if cursor.x != 0 {
+                self.move_to((cursor.x - 1, cursor.y));
+            }
+        }
+    }
+
+    // replays the last change count times
+    fn repeat(&mut self, count: usize) {
+        let keys = self.vim.as_ref().unwrap().last_change.clone();
+        for _ in 0..count {
+            for key in keys.iter() {
+                self.press(*key);
+            }
+        }
+    }
+
+    // keys in normal and visual mode the keymap doesn't bind
+    pub(crate) fn vim_key(&mut self, key: Key) {
+        let cursor = self.view().cursor;
+        let p = (cursor.x, cursor.y);
+        let vim = self.vim.as_mut().unwrap();
+        vim.keys.push(key);
+
+        let chr = match key {
+            Key::Char(chr) => chr,
+            Key::Esc => {
+                self.vim_normal();
+                return;
+            },
+            _ => {
+                vim.reset();
+                return;
+            }
+        };
+
+        // counts
+        if chr.is_ascii_digit() && (chr != '0' || vim.count != 0) {
+            vim.count = vim.count * 10 + chr.to_digit(10).unwrap() as usize;
+            return;
+        }
+
+        let counted = vim.count != 0 || vim.op_count != 0;
+        let visual = vim.mode == VimMode::Visual;
+
+        // gg is the only g command
+        if vim.g {
+            vim.g = false;
+            if chr != 'g' {
+                vim.reset();
+                return;
+            }
+        } else if chr == 'g' {
+            vim.g = true;
+            return;
+        }
+
+        // zz, zt and zb move the view
+        if vim.z {
+            vim.reset();
+            match chr {
+                'z' => self.align(Align::Center),
+                't' => self.align(Align::Top),
+                'b' => self.align(Align::Bottom),
+                _ => ()
+            }
+            return;
+        } else if chr == 'z' && vim.operator.is_none() {
+            vim.z = true;
+            return;
+        }
+
+        // a doubled operator works on lines
+        if let Some(operator) = vim.operator {
+            if chr == operator {
+                let count = vim.take_count();
+                self.operate(operator, p, (cursor.x, cursor.y + count - 1), Span::Linewise);
+                self.vim_done(operator != 'y');
+                return;
+            }
+        }
+
+        // operators
+        if "dcy".contains(chr) && !visual && vim.operator.is_none() {
+            vim.operator = Some(chr);
+            vim.op_count = mem::take(&mut vim.count);
+            return;
+        }
+
+        let operator_maybe = vim.operator;
+        let count = vim.take_count();
+
+        // cw changes to the end of the word
+        // and dw stops at the end of the line
+        let motion = match (operator_maybe, chr) {
+            (Some('c'), 'w') if self.class_at(p) != 0 => 'e',
+            _ => chr
+        };
+        // the last line of a large file has to be found first
+        if motion == 'G' || counted {
+            self.index_all();
+        }
+        if let Some((mut target, span)) = self.motion(motion, count, counted) {
+            if operator_maybe.is_some() && motion == 'w' && target.1 > cursor.y {
+                target = (self.buffer().line_len(cursor.y), cursor.y);
+            }
+
+            match operator_maybe {
+                Some(operator) => {
+                    self.operate(operator, p, target, span);
+                    self.vim_done(operator != 'y');
+                },
+                None => {
+                    self.move_cursor(target.0, target.1);
+                    if span != Span::Linewise || motion == 'g' || motion == 'G' {
+                        self.view_mut().cursor.fake_x = target.0;
+                    }
+                    self.vim_done(false);
+                }
+            }
+            return;
+        }
+
+        if operator_maybe.is_some() {
+            self.vim.as_mut().unwrap().reset();
+            return;
+        }
+
+        if visual {
+            self.visual_key(chr);
+            return;
+        }
+
+        let len = self.buffer().line_len(cursor.y);
+        match chr {
+            'x' => {
+                // a group of leading tabs is one char
+                let leading = self.leading_tabs(cursor.y);
+                let tab_width = self.buffer().tab_width();
+                let mut end = cursor.x;
+                for _ in 0..count {
+                    end = if end < leading {
+                        cmp::min(end - end % tab_width + tab_width, leading)
+                    } else {
+                        end + 1
+                    };
+                }
+                let end = cmp::min(end, len);
+                self.operate('d', p, (end, cursor.y), Span::Exclusive);
+                self.vim_done(true);
+            },
+            'p' | 'P' => {
+                self.paste(chr == 'p', count);
+                self.vim_done(true);
+            },
+            'i' | 'a' | 'I' | 'A' | 'o' | 'O' => {
+                match chr {
+                    'a' => self.move_to((cmp::min(cursor.x + 1, len), cursor.y)),
+                    'I' => self.move_to((self.first_non_blank(cursor.y), cursor.y)),
+                    'A' => self.move_to((len, cursor.y)),
+                    'o' => {
+                        self.move_to((len, cursor.y));
+                        self.newline();
+                    },
+                    'O' => {
+                        self.move_to((0, cursor.y));
+                        self.insert_raw("\n");
+                        self.move_to((0, cursor.y));
+                    },
+                    _ => ()
+                }
+                self.vim_insert();
+            },
+            'v' => {
+                let vim = self.vim.as_mut().unwrap();
+                vim.mode = VimMode::Visual;
+                vim.anchor = p;
+                self.vim_done(false);
+            },
+            '.' => {
+                self.vim.as_mut().unwrap().reset();
+                self.repeat(count);
+            },
+            ':' => {
+                self.vim.as_mut().unwrap().reset();
+                self.ask(Ask::Ex, "", "");
+            },
+            'u' => {
+                self.vim.as_mut().unwrap().reset();
+                self.undo();
+            },
+            _ => self.vim.as_mut().unwrap().reset()
+        }
+    }
+
+    // operators act on the selection right away
+    fn visual_key(&mut self, chr: char) {
+        let anchor = self.vim.as_ref().unwrap().anchor;
+        let cursor = self.view().cursor;
+        match chr {
+            'd' | 'x' | 'c' | 'y' => {
+                let operator = if chr == 'x' {
+                    'd'
+                } else {
+                    chr
+                };
+                self.vim.as_mut().unwrap().mode = VimMode::Normal;
+                self.operate(operator, anchor, (cursor.x, cursor.y), Span::Inclusive);
+
+                // selections can't be repeated with .
+                self.vim.as_mut().unwrap().recording = None;
+                self.vim_done(false);
+            },
+            'v' => self.vim_normal(),
+            ':' => {
+                self.vim_normal();
+                self.ask(Ask::Ex, "", "");
+            },
+            _ => self.vim.as_mut().unwrap().reset()
+        }
+    }
+
+    // a command is complete
+    // changes that stay in normal mode are kept for .
+    fn vim_done(&mut self, change: bool) {
+        let cursor = self.view().cursor;
+        let vim = self.vim.as_mut().unwrap();
+        if vim.mode != VimMode::Insert {
+            if change {
+                vim.last_change = mem::take(&mut vim.keys);
+            }
+            vim.reset();
+        }
+
+        let selection = match vim.mode {
+            VimMode::Visual => {
+                let (start, mut end) = view::ordered(vim.anchor, (cursor.x, cursor.y));
+                end.0 += 1;
+                Some((start, end))
+            },
+            _ => None
+        };
+        self.view_mut().selection = selection;
+    }
+
+    // :w, :q, :wq, :e <path>, :<line> and so on
+    // anything else is tried as a command name
+    pub(crate) fn ex(&mut self, text: &str) {
+        let text = text.trim();
+        let (name, arg) = match text.split_once(' ') {
+            Some((name, arg)) => (name, arg.trim()),
+            None => (text, "")
+        };
+
+        match name {
+            "" => (),
+            "w" => self.save(),
+            "q" | "qa" => self.request_quit(),
+            "q!" | "qa!" => self.quit = true,
+            "wq" | "x" => {
+                self.save();
+                self.request_quit();
+            },
+            "e" => if let Err(err) = self.open(Path::new(arg)) {
+                self.message = Some(format!("couldn't open {}: {}", arg, err));
+            },
+            "bn" => self.cycle(true),
+            "bp" => self.cycle(false),
+            "bd" => self.request_close(),
+            "bd!" => self.close(),
+            "sp" => self.split(Dir::Horizontal),
+            "vs" => self.split(Dir::Vertical),
+            _ if name.chars().all(|x| x.is_ascii_digit()) => self.go_to_line(name),
+            _ => match command::find(name) {
+                Some(command) => (command.run)(self),
+                None => self.message = Some(format!("not an editor command: {}", text))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::editor;
+
+    // text after typing keys in normal mode
+    // with \x1b for Esc
+    fn vim(text: &str, keys: &str) -> String {
+        let mut jedit = editor(text);
+        jedit.vim_toggle();
+        for chr in keys.chars() {
+            jedit.press(Key::from_char(chr).unwrap());
+        }
+        jedit.buffer().text()
+    }
+
+    #[test]
+    fn read_only_buffers_refuse_edits() {
+        let mut jedit = editor("abc");
+        jedit.vim_toggle();
+        jedit.toggle_readonly();
+        for chr in "xdd".chars() {
+            jedit.press(Key::from_char(chr).unwrap());
+        }
+        assert_eq!(jedit.buffer().text(), "abc");
+        assert_eq!(jedit.message.as_deref(), Some("[scratch] is read-only"));
+    }
+
+    #[test]
+    fn counts() {
+        assert_eq!(vim("abcdef", "3x"), "def");
+        assert_eq!(vim("one two three four", "2dw"), "three four");
+        assert_eq!(vim("one two three four", "d2w"), "three four");
+        assert_eq!(vim("a\nb\nc\nd", "2dd"), "c\nd");
+        assert_eq!(vim("a\nb\nc", "jyy2p"), "a\nb\nb\nb\nc");
+    }
+
+    #[test]
+    fn repeats() {
+        assert_eq!(vim("a b c d", "dw."), "c d");
+        assert_eq!(vim("a b c d", "dw2."), "d");
+        assert_eq!(vim("one two", "cwxy\x1bw."), "xy xy");
+    }
+
+    #[test]
+    fn operators() {
+        assert_eq!(vim("one two", "cwxy\x1b"), "xy two");
+        assert_eq!(vim("one two", "d$"), "");
+        assert_eq!(vim("one two", "wdb"), "two");
+        assert_eq!(vim("a\nb\nc", "jdd"), "a\nc");
+        assert_eq!(vim("one two", "vex"), " two");
+        assert_eq!(vim("one two", "ywP"), "one one two");
+    }
+
+    #[test]
+    fn leading_tabs_go_whole() {
+        assert_eq!(vim("\tfoo", "x"), "foo");
+        assert_eq!(vim("\t\tfoo", "2x"), "foo");
+        // from inside a group
+        assert_eq!(vim("\t\tfoo", "llx"), "\tfoo");
+        assert_eq!(vim("\t\tfoo", "dl"), "\tfoo");
+        assert_eq!(vim("\t\tfoo", "$hhdh"), "\tfoo");
+        assert_eq!(vim("\t\tfoo", "lld$"), "");
+        assert_eq!(vim("\t\tfoo", "lllvlx"), "foo");
+        // tabs after the indentation are single chars
+        assert_eq!(vim("a\t\tb", "lx"), "a\tb");
+    }
+}
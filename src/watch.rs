@@ -0,0 +1,120 @@
+use std::cmp;
+use std::fs;
+use std::time::{Duration, Instant};
+
+use crate::format;
+use crate::merge;
+use crate::prompt::Ask;
+use crate::JEdit;
+
+// notices files changed by other programs
+// by polling their modification times
+
+const INTERVAL: Duration = Duration::from_secs(2);
+
+impl JEdit {
+    // reloads unmodified buffers and asks about modified ones
+    // one question at a time
+    pub(crate) fn check_disk(&mut self) {
+        if self.last_check.elapsed() < INTERVAL || self.prompt.is_some() {
+            return;
+        }
+        self.last_check = Instant::now();
+
+        for i in 0..self.buffers.len() {
+            // large files are never read whole
+            let buffer = &self.buffers[i];
+            if buffer.is_large() {
+                continue;
+            }
+            let path = match buffer.path() {
+                Some(path) => path.to_path_buf(),
+                None => continue
+            };
+
+            // deleted files keep their buffer
+            let time = match fs::metadata(&path).and_then(|x| x.modified()) {
+                Ok(time) => Some(time),
+                Err(_) => continue
+            };
+            if time == buffer.disk_time() {
+                continue;
+            }
+
+            // in the encoding the buffer was opened with
+            let (text, format) = match format::read(&path, Some(buffer.format().encoding)) {
+                Ok(read) => read,
+                Err(_) => continue
+            };
+            if text == buffer.disk_text() && format == buffer.format() {
+                self.buffers[i].set_disk(time, text);
+                continue;
+            }
+
+            if buffer.modified() {
+                self.changed = Some((i, time, text, format));
+                let label = format!("{} changed on disk, merge, reload or keep yours? [m/r/k]", buffer.name());
+                self.ask(Ask::Changed, label.as_str(), "");
+                return;
+            }
+
+            self.reload(i, text.as_str());
+            self.buffers[i].set_format(format);
+            self.buffers[i].mark_saved();
+            self.buffers[i].set_disk(time, text);
+            self.message = Some(format!("reloaded {}", self.buffers[i].name()));
+        }
+    }
+
+    // replaces the text of buffer i
+    // and keeps the cursor near where it was
+    fn reload(&mut self, i: usize, text: &str) {
+        let focused = self.current();
+        self.switch(i);
+
+        let cursor = self.view().cursor;
+        self.replace_text(text);
+
+        let y = cmp::min(cursor.y, self.buffer().height().saturating_sub(1));
+        let x = cmp::min(cursor.x, self.buffer().line_len(y));
+        self.move_cursor(x, y);
+        self.view_mut().cursor.fake_x = x;
+
+        self.switch(focused);
+    }
+
+    pub(crate) fn answer_changed(&mut self, answer: &str) {
+        let (i, time, text, format) = match self.changed.take() {
+            Some(changed) => changed,
+            None => return
+        };
+
+        match answer {
+            "m" => {
+                let ours = self.buffers[i].text();
+                let (merged, conflicts) = merge::merge(self.buffers[i].disk_text(), ours.as_str(), text.as_str());
+
+                // the disk text is the new saved state
+                let ours_format = self.buffers[i].format();
+                self.reload(i, text.as_str());
+                self.buffers[i].set_format(format);
+                self.buffers[i].mark_saved();
+                self.reload(i, merged.as_str());
+                self.buffers[i].set_format(ours_format);
+                self.message = Some(match conflicts {
+                    0 => format!("merged {}", self.buffers[i].name()),
+                    _ => format!("merged {} with {} conflicts", self.buffers[i].name(), conflicts)
+                });
+            },
+            "r" => {
+                self.reload(i, text.as_str());
+                self.buffers[i].set_format(format);
+                self.buffers[i].mark_saved();
+            },
+            _ => ()
+        }
+
+        // keeping yours still counts the new text as seen
+        self.buffers[i].set_disk(time, text);
+    }
+}